@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE face_rejections;
+
+ALTER TABLE faces
+    DROP COLUMN is_ignored,
+    DROP COLUMN is_manual;
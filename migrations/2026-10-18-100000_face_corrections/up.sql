@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE faces
+    ADD COLUMN is_ignored BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN is_manual BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Лица, вручную убранные из личности. Используются при автоматическом
+-- сопоставлении, чтобы не назначать похожие лица обратно в эту личность.
+CREATE TABLE face_rejections (
+    face_id INT NOT NULL,
+    person_id INT NOT NULL,
+    PRIMARY KEY (face_id, person_id),
+    CONSTRAINT fk_face_rejections_faces
+      FOREIGN KEY(face_id)
+        REFERENCES faces(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_face_rejections_persons
+      FOREIGN KEY(person_id)
+        REFERENCES persons(id)
+        ON DELETE CASCADE
+);
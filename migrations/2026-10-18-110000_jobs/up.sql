@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE jobs (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR (50) NOT NULL,
+    photo_id INT,
+    status VARCHAR (20) NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL DEFAULT 5,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    locked_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_jobs_photos
+      FOREIGN KEY(photo_id)
+        REFERENCES photos(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX jobs_queue_idx ON jobs (status, run_at);
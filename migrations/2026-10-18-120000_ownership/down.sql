@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX photos_user_id_idx;
+
+ALTER TABLE persons DROP COLUMN user_id;
+
+ALTER TABLE albums DROP COLUMN user_id;
@@ -0,0 +1,47 @@
+-- Your SQL goes here
+ALTER TABLE albums ADD COLUMN user_id INT;
+
+-- Альбом достаётся владельцу его фотографий, пустой - первому администратору,
+-- а без администраторов - первому пользователю
+UPDATE albums SET user_id = COALESCE(
+    (SELECT MIN(photos.user_id) FROM photos WHERE photos.album_id = albums.id),
+    (SELECT MIN(id) FROM users WHERE is_admin),
+    (SELECT MIN(id) FROM users)
+);
+
+-- Владелец не найден только без пользователей, и тогда альбом заведомо пуст
+DELETE FROM albums WHERE user_id IS NULL;
+
+ALTER TABLE albums
+    ALTER COLUMN user_id SET NOT NULL,
+    ADD CONSTRAINT fk_albums_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE;
+
+CREATE INDEX albums_user_id_idx ON albums (user_id);
+
+
+ALTER TABLE persons ADD COLUMN user_id INT;
+
+UPDATE persons SET user_id = COALESCE(
+    (SELECT MIN(photos.user_id)
+       FROM faces
+       JOIN photos ON photos.id = faces.photo_id
+      WHERE faces.person_id = persons.id),
+    (SELECT MIN(id) FROM users WHERE is_admin),
+    (SELECT MIN(id) FROM users)
+);
+
+DELETE FROM persons WHERE user_id IS NULL;
+
+ALTER TABLE persons
+    ALTER COLUMN user_id SET NOT NULL,
+    ADD CONSTRAINT fk_persons_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE;
+
+CREATE INDEX persons_user_id_idx ON persons (user_id);
+
+CREATE INDEX photos_user_id_idx ON photos (user_id);
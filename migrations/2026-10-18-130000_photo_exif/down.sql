@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE photos
+    DROP COLUMN taken_at,
+    DROP COLUMN camera_make,
+    DROP COLUMN camera_model,
+    DROP COLUMN lens_model,
+    DROP COLUMN exposure_time,
+    DROP COLUMN f_number,
+    DROP COLUMN iso,
+    DROP COLUMN focal_length,
+    DROP COLUMN orientation,
+    DROP COLUMN latitude,
+    DROP COLUMN longitude;
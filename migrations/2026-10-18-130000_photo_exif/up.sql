@@ -0,0 +1,16 @@
+-- Your SQL goes here
+ALTER TABLE photos
+    ADD COLUMN taken_at TIMESTAMP,
+    ADD COLUMN camera_make VARCHAR (100),
+    ADD COLUMN camera_model VARCHAR (100),
+    ADD COLUMN lens_model VARCHAR (100),
+    ADD COLUMN exposure_time DOUBLE PRECISION,
+    ADD COLUMN f_number DOUBLE PRECISION,
+    ADD COLUMN iso INT,
+    ADD COLUMN focal_length DOUBLE PRECISION,
+    ADD COLUMN orientation SMALLINT,
+    ADD COLUMN latitude DOUBLE PRECISION,
+    ADD COLUMN longitude DOUBLE PRECISION;
+
+CREATE INDEX photos_taken_at_idx ON photos (taken_at);
+CREATE INDEX photos_camera_idx ON photos (camera_make, camera_model);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE photos
+    DROP COLUMN original_path,
+    DROP COLUMN mime_type;
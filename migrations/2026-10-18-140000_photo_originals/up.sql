@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE photos
+    ADD COLUMN original_path TEXT,
+    ADD COLUMN mime_type VARCHAR (100);
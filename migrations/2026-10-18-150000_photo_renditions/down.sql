@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE photos
+    DROP COLUMN thumbnail_path,
+    DROP COLUMN preview_path,
+    DROP COLUMN thumbnail_webp_path,
+    DROP COLUMN preview_webp_path;
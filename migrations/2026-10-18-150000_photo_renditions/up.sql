@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE photos
+    ADD COLUMN thumbnail_path TEXT,
+    ADD COLUMN preview_path TEXT,
+    ADD COLUMN thumbnail_webp_path TEXT,
+    ADD COLUMN preview_webp_path TEXT;
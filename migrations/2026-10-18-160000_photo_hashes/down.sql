@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX photos_user_content_hash_idx;
+
+ALTER TABLE photos
+    DROP COLUMN content_hash,
+    DROP COLUMN perceptual_hash;
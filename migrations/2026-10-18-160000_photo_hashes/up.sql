@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE photos
+    ADD COLUMN content_hash VARCHAR (64),
+    ADD COLUMN perceptual_hash BIGINT;
+
+-- Один и тот же файл пользователь может загрузить только один раз
+CREATE UNIQUE INDEX photos_user_content_hash_idx ON photos (user_id, content_hash);
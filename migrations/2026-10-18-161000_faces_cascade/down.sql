@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE faces
+    DROP CONSTRAINT fk_faces_photos,
+    ADD CONSTRAINT fk_faces_photos
+      FOREIGN KEY(photo_id)
+        REFERENCES photos(id);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Лица удаляются вместе с изображением, на это полагается удаление
+-- фотографий и дубликатов
+ALTER TABLE faces
+    DROP CONSTRAINT fk_faces_photos,
+    ADD CONSTRAINT fk_faces_photos
+      FOREIGN KEY(photo_id)
+        REFERENCES photos(id)
+        ON DELETE CASCADE;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Поиск ближайших по CLIP эмбеддингу фотографий, в том числе кандидатов
+-- в дубликаты
+CREATE INDEX photos_embedding_idx ON photos USING hnsw (embedding vector_cosine_ops);
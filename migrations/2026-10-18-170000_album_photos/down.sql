@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE albums DROP COLUMN cover_photo_id;
+
+ALTER TABLE photos
+    ADD COLUMN album_id INT,
+    ADD CONSTRAINT fk_photos_albums
+      FOREIGN KEY(album_id)
+        REFERENCES albums(id);
+
+-- Из нескольких альбомов фотографии остаётся первый добавленный
+UPDATE photos
+SET album_id = first_album.album_id
+FROM (
+    SELECT DISTINCT ON (photo_id) photo_id, album_id
+    FROM album_photos
+    ORDER BY photo_id, added_at, album_id
+) AS first_album
+WHERE photos.id = first_album.photo_id;
+
+DROP TABLE album_photos;
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+CREATE TABLE album_photos (
+    album_id INT NOT NULL,
+    photo_id INT NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (album_id, photo_id),
+    CONSTRAINT fk_album_photos_albums
+      FOREIGN KEY(album_id)
+        REFERENCES albums(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_album_photos_photos
+      FOREIGN KEY(photo_id)
+        REFERENCES photos(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX album_photos_photo_id_idx ON album_photos (photo_id);
+
+INSERT INTO album_photos (album_id, photo_id)
+SELECT album_id, id FROM photos WHERE album_id IS NOT NULL;
+
+ALTER TABLE photos DROP COLUMN album_id;
+
+ALTER TABLE albums
+    ADD COLUMN cover_photo_id INT,
+    ADD CONSTRAINT fk_albums_cover_photo
+      FOREIGN KEY(cover_photo_id)
+        REFERENCES photos(id)
+        ON DELETE SET NULL;
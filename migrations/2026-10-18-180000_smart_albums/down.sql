@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX albums_smart_idx;
+
+ALTER TABLE albums
+    DROP COLUMN rule,
+    DROP COLUMN rule_embedding;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Правило умного альбома и CLIP эмбеддинг его текстового запроса
+ALTER TABLE albums
+    ADD COLUMN rule JSONB,
+    ADD COLUMN rule_embedding VECTOR(512);
+
+CREATE INDEX albums_smart_idx ON albums (user_id) WHERE rule IS NOT NULL;
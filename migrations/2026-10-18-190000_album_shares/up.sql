@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE album_shares (
+    album_id INT NOT NULL,
+    user_id INT NOT NULL,
+    role VARCHAR (20) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (album_id, user_id),
+    CONSTRAINT fk_album_shares_albums
+      FOREIGN KEY(album_id)
+        REFERENCES albums(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_album_shares_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE,
+    CONSTRAINT album_shares_role_check
+      CHECK (role IN ('viewer', 'contributor', 'editor'))
+);
+
+CREATE INDEX album_shares_user_id_idx ON album_shares (user_id);
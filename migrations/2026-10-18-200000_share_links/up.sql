@@ -0,0 +1,30 @@
+-- Your SQL goes here
+CREATE TABLE share_links (
+    id SERIAL PRIMARY KEY,
+    token VARCHAR (64) NOT NULL UNIQUE,
+    user_id INT NOT NULL,
+    album_id INT,
+    photo_id INT,
+    password VARCHAR (255),
+    allow_download BOOLEAN NOT NULL DEFAULT FALSE,
+    expires_at TIMESTAMPTZ,
+    view_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_share_links_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_share_links_albums
+      FOREIGN KEY(album_id)
+        REFERENCES albums(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_share_links_photos
+      FOREIGN KEY(photo_id)
+        REFERENCES photos(id)
+        ON DELETE CASCADE,
+    -- Ссылка ведёт либо на альбом, либо на одну фотографию
+    CONSTRAINT share_links_target_check
+      CHECK ((album_id IS NULL) <> (photo_id IS NULL))
+);
+
+CREATE INDEX share_links_user_id_idx ON share_links (user_id);
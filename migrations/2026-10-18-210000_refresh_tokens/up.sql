@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    -- Общий для всех токенов одного входа, в него же входит токен доступа
+    session_id VARCHAR (64) NOT NULL,
+    user_id INT NOT NULL,
+    -- SHA-256 токена, сам токен хранится только в cookie
+    token_hash VARCHAR (64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    -- Заполняется при обновлении и выходе
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_refresh_tokens_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX refresh_tokens_session_id_idx ON refresh_tokens (session_id);
+CREATE INDEX refresh_tokens_user_id_idx ON refresh_tokens (user_id);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    name VARCHAR (100) NOT NULL,
+    -- Начало ключа, по которому его можно узнать в списке
+    prefix VARCHAR (16) NOT NULL,
+    -- SHA-256 ключа, сам ключ показывается только при создании
+    key_hash VARCHAR (64) NOT NULL UNIQUE,
+    scope VARCHAR (20) NOT NULL,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_api_keys_users
+      FOREIGN KEY(user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE,
+    CONSTRAINT api_keys_scope_check
+      CHECK (scope IN ('read', 'upload', 'write'))
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE invites (
+    id SERIAL PRIMARY KEY,
+    code VARCHAR (64) NOT NULL UNIQUE,
+    created_by INT NOT NULL,
+    -- Почта, для которой выдано приглашение, пусто - для любой
+    email VARCHAR (50),
+    expires_at TIMESTAMPTZ,
+    used_by INT,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_invites_created_by
+      FOREIGN KEY(created_by)
+        REFERENCES users(id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_invites_used_by
+      FOREIGN KEY(used_by)
+        REFERENCES users(id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX invites_created_by_idx ON invites (created_by);
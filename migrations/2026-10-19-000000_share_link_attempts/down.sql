@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE share_links
+    DROP COLUMN locked_until,
+    DROP COLUMN failed_attempts;
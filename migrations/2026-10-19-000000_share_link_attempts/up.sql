@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Неудачные попытки пароля ссылки и блокировка подбора после них
+ALTER TABLE share_links
+    ADD COLUMN failed_attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN locked_until TIMESTAMPTZ;
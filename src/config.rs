@@ -0,0 +1,677 @@
+use std::{collections::HashMap, env, fs, net::SocketAddr, path::Path, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::errors::ConfigError;
+
+/// Файл настроек по умолчанию, его отсутствие не считается ошибкой
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Секреты, которые когда-то лежали в репозитории, с ними подпись можно
+/// подделать
+const KNOWN_SECRETS: &[&str] = &["awdawd", "awdawd-media"];
+
+/// Настройки приложения: значения по умолчанию, затем TOML файл
+/// (`CONFIG_PATH` или `config.toml`), затем переменные окружения
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub ml: MlConfig,
+    pub storage: StorageConfig,
+    pub s3: S3Config,
+    pub renditions: RenditionsConfig,
+    pub auth: AuthConfig,
+    pub media: MediaConfig,
+    pub recognition: RecognitionConfig,
+    pub duplicates: DuplicatesConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Адрес, на котором слушает HTTP сервер
+    pub bind_address: String,
+    /// Максимальный размер тела запроса в байтах
+    pub body_limit: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0:3000".to_string(),
+            body_limit: 100_000_000,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Строка подключения к Postgres
+    pub url: String,
+    /// Размер пула соединений
+    pub pool_size: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            pool_size: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MlBackendKind {
+    /// Python сервис распознавания
+    #[default]
+    Http,
+    /// Детерминированная заглушка для разработки без ML сервиса
+    Fake,
+}
+
+impl FromStr for MlBackendKind {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "http" => Ok(MlBackendKind::Http),
+            "fake" => Ok(MlBackendKind::Fake),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MlConfig {
+    pub backend: MlBackendKind,
+    /// Адрес ML сервиса
+    pub url: String,
+    /// Таймаут запроса целиком в секундах
+    pub timeout_secs: u64,
+    /// Таймаут установки соединения в секундах
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for MlConfig {
+    fn default() -> Self {
+        MlConfig {
+            backend: MlBackendKind::Http,
+            url: "http://0.0.0.0:3003".to_string(),
+            timeout_secs: 60,
+            connect_timeout_secs: 5,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendKind {
+    /// Файлы на локальном диске
+    #[default]
+    Local,
+    /// S3 совместимое хранилище объектов
+    S3,
+}
+
+impl FromStr for StorageBackendKind {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "local" => Ok(StorageBackendKind::Local),
+            "s3" => Ok(StorageBackendKind::S3),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Каталоги задают и ключи файлов: в S3 ключ совпадает с путём, который
+/// файл имел бы на локальном диске
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackendKind,
+    /// Корневой каталог файлов
+    pub root: String,
+    /// JPEG для показа и ML, должен находиться внутри `root`
+    pub images_dir: String,
+    /// Вырезанные лица, должен находиться внутри `root`
+    pub faces_dir: String,
+    /// Исходные загруженные файлы без изменений, должен находиться внутри `root`
+    pub originals_dir: String,
+    /// Миниатюры и превью, должен находиться внутри `root`
+    pub renditions_dir: String,
+    /// Аватары пользователей, должен находиться внутри `root`
+    pub avatars_dir: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: StorageBackendKind::Local,
+            root: "storage".to_string(),
+            images_dir: "storage/images".to_string(),
+            faces_dir: "storage/faces".to_string(),
+            originals_dir: "storage/originals".to_string(),
+            renditions_dir: "storage/renditions".to_string(),
+            avatars_dir: "storage/avatars".to_string(),
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn dirs(&self) -> [&str; 5] {
+        [
+            &self.images_dir,
+            &self.faces_dir,
+            &self.originals_dir,
+            &self.renditions_dir,
+            &self.avatars_dir,
+        ]
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// Адрес S3 API, например `http://localhost:9000`
+    pub endpoint: String,
+    /// Бакет, к которому обращаются по адресу `{endpoint}/{bucket}`
+    pub bucket: String,
+    /// Регион подписи запросов
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Таймаут запроса целиком в секундах
+    pub timeout_secs: u64,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        S3Config {
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenditionsConfig {
+    /// Сторона квадратной миниатюры в пикселях
+    pub thumbnail_size: u32,
+    /// Максимальная сторона превью в пикселях
+    pub preview_size: u32,
+    /// Качество JPEG от 1 до 100
+    pub jpeg_quality: u8,
+    /// Дополнительно сохранять копии в WebP (без потерь)
+    pub webp: bool,
+}
+
+impl Default for RenditionsConfig {
+    fn default() -> Self {
+        RenditionsConfig {
+            thumbnail_size: 256,
+            preview_size: 1280,
+            jpeg_quality: 85,
+            webp: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Секрет подписи JWT
+    pub jwt_secret: String,
+    /// `kid` секрета `jwt_secret` в заголовке JWT
+    pub jwt_key_id: String,
+    /// Прежние секреты по `kid`: ими проверяются уже выданные токены, пока те
+    /// не истекут, новые токены ими не подписываются
+    pub previous_jwt_secrets: JwtSecrets,
+    /// Время жизни токена доступа в секундах
+    pub token_lifetime_secs: i64,
+    /// Время жизни токена обновления в секундах, продлевается при каждом
+    /// обновлении
+    pub refresh_token_lifetime_secs: i64,
+    /// Атрибут `Secure` cookie с токенами
+    pub cookie_secure: bool,
+    /// Атрибут `SameSite` cookie с токенами
+    pub cookie_same_site: CookieSameSite,
+    /// Разрешена регистрация по приглашениям администратора
+    pub registration_enabled: bool,
+    /// Администраторы видят фотографии, альбомы и личности всех
+    /// пользователей, а не только свои
+    pub admins_see_all: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: String::new(),
+            jwt_key_id: "default".to_string(),
+            previous_jwt_secrets: JwtSecrets::default(),
+            token_lifetime_secs: 15 * 60,
+            refresh_token_lifetime_secs: 30 * 24 * 60 * 60,
+            cookie_secure: true,
+            cookie_same_site: CookieSameSite::Lax,
+            registration_enabled: false,
+            admins_see_all: true,
+        }
+    }
+}
+
+/// Секреты JWT по `kid`, в переменной окружения задаются как
+/// `kid=secret,kid=secret`
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct JwtSecrets(pub HashMap<String, String>);
+
+impl FromStr for JwtSecrets {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .filter(|pair| !pair.trim().is_empty())
+            .map(|pair| {
+                let (kid, secret) = pair.split_once('=').ok_or(())?;
+                Ok((kid.trim().to_string(), secret.trim().to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(JwtSecrets)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    #[default]
+    Lax,
+    /// Требует `cookie_secure`
+    None,
+}
+
+impl FromStr for CookieSameSite {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "strict" => Ok(CookieSameSite::Strict),
+            "lax" => Ok(CookieSameSite::Lax),
+            "none" => Ok(CookieSameSite::None),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaConfig {
+    /// Секрет подписи ссылок на файлы, отдельный от секретов JWT, чтобы их
+    /// смена не ломала выданные ссылки
+    pub signing_secret: String,
+    /// Время жизни подписанной ссылки на файл в секундах
+    pub signed_url_lifetime_secs: i64,
+    /// `max-age` заголовка `Cache-Control` у файлов в секундах
+    pub cache_max_age_secs: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            signing_secret: String::new(),
+            signed_url_lifetime_secs: 5 * 60,
+            cache_max_age_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecognitionConfig {
+    /// Максимальное косинусное расстояние, при котором лица считаются
+    /// принадлежащими одной личности
+    pub face_distance_threshold: f64,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        RecognitionConfig {
+            face_distance_threshold: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DuplicatesConfig {
+    /// Максимальное число различающихся бит перцептивного хэша у
+    /// почти одинаковых изображений
+    pub hash_distance: u32,
+    /// Максимальное косинусное расстояние CLIP эмбеддингов почти одинаковых
+    /// изображений
+    pub embedding_distance: f64,
+}
+
+impl Default for DuplicatesConfig {
+    fn default() -> Self {
+        DuplicatesConfig {
+            hash_distance: 6,
+            embedding_distance: 0.05,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::var("CONFIG_PATH") {
+            Ok(path) => Config::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Config::from_file(DEFAULT_CONFIG_PATH)?
+            }
+            Err(_) => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_string(),
+            source,
+        })?;
+        toml::from_str(&content).map_err(|source| ConfigError::Parse {
+            path: path.to_string(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_override("BIND_ADDRESS", &mut self.server.bind_address)?;
+        env_override("BODY_LIMIT", &mut self.server.body_limit)?;
+        env_override("DATABASE_URL", &mut self.database.url)?;
+        env_override("DATABASE_POOL_SIZE", &mut self.database.pool_size)?;
+        env_override("ML_BACKEND", &mut self.ml.backend)?;
+        env_override("ML_URL", &mut self.ml.url)?;
+        env_override("ML_TIMEOUT_SECS", &mut self.ml.timeout_secs)?;
+        env_override("ML_CONNECT_TIMEOUT_SECS", &mut self.ml.connect_timeout_secs)?;
+        env_override("STORAGE_BACKEND", &mut self.storage.backend)?;
+        env_override("STORAGE_ROOT", &mut self.storage.root)?;
+        env_override("STORAGE_IMAGES_DIR", &mut self.storage.images_dir)?;
+        env_override("STORAGE_FACES_DIR", &mut self.storage.faces_dir)?;
+        env_override("STORAGE_ORIGINALS_DIR", &mut self.storage.originals_dir)?;
+        env_override("STORAGE_RENDITIONS_DIR", &mut self.storage.renditions_dir)?;
+        env_override("STORAGE_AVATARS_DIR", &mut self.storage.avatars_dir)?;
+        env_override("S3_ENDPOINT", &mut self.s3.endpoint)?;
+        env_override("S3_BUCKET", &mut self.s3.bucket)?;
+        env_override("S3_REGION", &mut self.s3.region)?;
+        env_override("S3_ACCESS_KEY", &mut self.s3.access_key)?;
+        env_override("S3_SECRET_KEY", &mut self.s3.secret_key)?;
+        env_override("S3_TIMEOUT_SECS", &mut self.s3.timeout_secs)?;
+        env_override("THUMBNAIL_SIZE", &mut self.renditions.thumbnail_size)?;
+        env_override("PREVIEW_SIZE", &mut self.renditions.preview_size)?;
+        env_override("JPEG_QUALITY", &mut self.renditions.jpeg_quality)?;
+        env_override("RENDITIONS_WEBP", &mut self.renditions.webp)?;
+        env_override("JWT_SECRET", &mut self.auth.jwt_secret)?;
+        env_override("JWT_KEY_ID", &mut self.auth.jwt_key_id)?;
+        env_override("JWT_PREVIOUS_SECRETS", &mut self.auth.previous_jwt_secrets)?;
+        env_override("TOKEN_LIFETIME_SECS", &mut self.auth.token_lifetime_secs)?;
+        env_override(
+            "REFRESH_TOKEN_LIFETIME_SECS",
+            &mut self.auth.refresh_token_lifetime_secs,
+        )?;
+        env_override("COOKIE_SECURE", &mut self.auth.cookie_secure)?;
+        env_override("COOKIE_SAME_SITE", &mut self.auth.cookie_same_site)?;
+        env_override("REGISTRATION_ENABLED", &mut self.auth.registration_enabled)?;
+        env_override("ADMINS_SEE_ALL", &mut self.auth.admins_see_all)?;
+        env_override("MEDIA_SIGNING_SECRET", &mut self.media.signing_secret)?;
+        env_override(
+            "MEDIA_SIGNED_URL_LIFETIME_SECS",
+            &mut self.media.signed_url_lifetime_secs,
+        )?;
+        env_override(
+            "MEDIA_CACHE_MAX_AGE_SECS",
+            &mut self.media.cache_max_age_secs,
+        )?;
+        env_override(
+            "FACE_DISTANCE_THRESHOLD",
+            &mut self.recognition.face_distance_threshold,
+        )?;
+        env_override(
+            "DUPLICATE_HASH_DISTANCE",
+            &mut self.duplicates.hash_distance,
+        )?;
+        env_override(
+            "DUPLICATE_EMBEDDING_DISTANCE",
+            &mut self.duplicates.embedding_distance,
+        )?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: &str| Err(ConfigError::Invalid(message.to_string()));
+
+        if self.server.bind_address.parse::<SocketAddr>().is_err() {
+            return invalid("server.bind_address must be a socket address like 0.0.0.0:3000");
+        }
+        if self.server.body_limit == 0 {
+            return invalid("server.body_limit must be positive");
+        }
+        if self.database.url.is_empty() {
+            return invalid("database.url (DATABASE_URL) must be set");
+        }
+        if self.database.pool_size == 0 {
+            return invalid("database.pool_size must be positive");
+        }
+        if self.ml.backend == MlBackendKind::Http && reqwest::Url::parse(&self.ml.url).is_err() {
+            return invalid("ml.url must be a valid URL");
+        }
+        if self.ml.timeout_secs == 0 || self.ml.connect_timeout_secs == 0 {
+            return invalid("ml timeouts must be positive");
+        }
+        if self.storage.dirs().iter().any(|dir| dir.is_empty()) {
+            return invalid("storage directories must not be empty");
+        }
+        let root = Path::new(&self.storage.root);
+        if !self
+            .storage
+            .dirs()
+            .iter()
+            .all(|dir| Path::new(dir).starts_with(root))
+        {
+            return invalid("storage directories must be inside storage.root");
+        }
+        if self.storage.backend == StorageBackendKind::S3 {
+            if reqwest::Url::parse(&self.s3.endpoint).is_err() {
+                return invalid("s3.endpoint (S3_ENDPOINT) must be a valid URL");
+            }
+            if self.s3.bucket.is_empty() || self.s3.region.is_empty() {
+                return invalid("s3.bucket and s3.region must be set");
+            }
+            if self.s3.access_key.is_empty() || self.s3.secret_key.is_empty() {
+                return invalid("s3.access_key and s3.secret_key must be set");
+            }
+            if self.s3.timeout_secs == 0 {
+                return invalid("s3.timeout_secs must be positive");
+            }
+        }
+        if self.renditions.thumbnail_size == 0 || self.renditions.preview_size == 0 {
+            return invalid("renditions sizes must be positive");
+        }
+        if !(1..=100).contains(&self.renditions.jpeg_quality) {
+            return invalid("renditions.jpeg_quality must be in [1, 100]");
+        }
+        if self.auth.jwt_secret.is_empty() {
+            return invalid("auth.jwt_secret (JWT_SECRET) must be set");
+        }
+        if KNOWN_SECRETS.contains(&self.auth.jwt_secret.as_str()) {
+            return invalid("auth.jwt_secret (JWT_SECRET) must not be a published example value");
+        }
+        if self.auth.jwt_key_id.is_empty() {
+            return invalid("auth.jwt_key_id must not be empty");
+        }
+        if self
+            .auth
+            .previous_jwt_secrets
+            .0
+            .contains_key(&self.auth.jwt_key_id)
+        {
+            return invalid("auth.previous_jwt_secrets must not contain auth.jwt_key_id");
+        }
+        if self
+            .auth
+            .previous_jwt_secrets
+            .0
+            .values()
+            .any(String::is_empty)
+        {
+            return invalid("auth.previous_jwt_secrets must not contain empty secrets");
+        }
+        if self.auth.token_lifetime_secs <= 0 {
+            return invalid("auth.token_lifetime_secs must be positive");
+        }
+        if self.auth.refresh_token_lifetime_secs <= self.auth.token_lifetime_secs {
+            return invalid(
+                "auth.refresh_token_lifetime_secs must be longer than auth.token_lifetime_secs",
+            );
+        }
+        if self.auth.cookie_same_site == CookieSameSite::None && !self.auth.cookie_secure {
+            return invalid("auth.cookie_same_site = \"none\" requires auth.cookie_secure");
+        }
+        if self.media.signing_secret.is_empty() {
+            return invalid("media.signing_secret (MEDIA_SIGNING_SECRET) must be set");
+        }
+        if KNOWN_SECRETS.contains(&self.media.signing_secret.as_str()) {
+            return invalid(
+                "media.signing_secret (MEDIA_SIGNING_SECRET) must not be a published example value",
+            );
+        }
+        if self.media.signing_secret == self.auth.jwt_secret {
+            return invalid("media.signing_secret must differ from auth.jwt_secret");
+        }
+        if self.media.signed_url_lifetime_secs <= 0 {
+            return invalid("media.signed_url_lifetime_secs must be positive");
+        }
+        if !(self.recognition.face_distance_threshold > 0.0
+            && self.recognition.face_distance_threshold <= 2.0)
+        {
+            return invalid("recognition.face_distance_threshold must be in (0, 2]");
+        }
+        if self.duplicates.hash_distance > 64 {
+            return invalid("duplicates.hash_distance must be at most 64");
+        }
+        if !(0.0..=2.0).contains(&self.duplicates.embedding_distance) {
+            return invalid("duplicates.embedding_distance must be in [0, 2]");
+        }
+        Ok(())
+    }
+}
+
+fn env_override<T: FromStr>(name: &'static str, field: &mut T) -> Result<(), ConfigError> {
+    if let Ok(value) = env::var(name) {
+        *field = value
+            .parse()
+            .map_err(|_| ConfigError::Env { name, value })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Настройки, которые проходят проверку
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.database.url = "postgres://localhost/recognition".to_string();
+        config.auth.jwt_secret = "jwt-test-secret".to_string();
+        config.media.signing_secret = "media-test-secret".to_string();
+        config
+    }
+
+    #[test]
+    fn valid_config_passes_validation() {
+        assert!(valid_config().validate().is_ok());
+    }
+
+    #[test]
+    fn empty_or_published_secrets_are_rejected() {
+        for secret in ["", "awdawd", "awdawd-media"] {
+            let mut config = valid_config();
+            config.media.signing_secret = secret.to_string();
+            assert!(config.validate().is_err(), "media secret {secret:?}");
+
+            let mut config = valid_config();
+            config.auth.jwt_secret = secret.to_string();
+            assert!(config.validate().is_err(), "jwt secret {secret:?}");
+        }
+    }
+
+    #[test]
+    fn media_secret_must_differ_from_jwt_secret() {
+        let mut config = valid_config();
+        config.media.signing_secret = config.auth.jwt_secret.clone();
+        assert!(config.validate().is_err());
+    }
+
+    // Переменные окружения общие для всех тестов процесса, поэтому у
+    // каждого теста свои имена
+
+    #[test]
+    fn env_override_keeps_value_without_variable() {
+        let mut pool_size = 5u32;
+        env_override("RECOGNITION_TEST_UNSET", &mut pool_size).unwrap();
+        assert_eq!(pool_size, 5);
+    }
+
+    #[test]
+    fn env_override_parses_variable() {
+        env::set_var("RECOGNITION_TEST_POOL_SIZE", "12");
+        env::set_var("RECOGNITION_TEST_ML_BACKEND", "fake");
+        env::set_var("RECOGNITION_TEST_SECRETS", "old=first, older=second,");
+
+        let mut pool_size = 5u32;
+        env_override("RECOGNITION_TEST_POOL_SIZE", &mut pool_size).unwrap();
+        assert_eq!(pool_size, 12);
+
+        let mut backend = MlBackendKind::Http;
+        env_override("RECOGNITION_TEST_ML_BACKEND", &mut backend).unwrap();
+        assert_eq!(backend, MlBackendKind::Fake);
+
+        let mut secrets = JwtSecrets::default();
+        env_override("RECOGNITION_TEST_SECRETS", &mut secrets).unwrap();
+        assert_eq!(secrets.0.len(), 2);
+        assert_eq!(secrets.0["old"], "first");
+        assert_eq!(secrets.0["older"], "second");
+    }
+
+    #[test]
+    fn env_override_rejects_invalid_value() {
+        env::set_var("RECOGNITION_TEST_BAD_POOL_SIZE", "many");
+        env::set_var("RECOGNITION_TEST_BAD_SECRETS", "no-key-id");
+
+        let mut pool_size = 5u32;
+        assert!(matches!(
+            env_override("RECOGNITION_TEST_BAD_POOL_SIZE", &mut pool_size),
+            Err(ConfigError::Env { name: "RECOGNITION_TEST_BAD_POOL_SIZE", ref value }) if value == "many"
+        ));
+        assert_eq!(pool_size, 5);
+
+        let mut secrets = JwtSecrets::default();
+        assert!(env_override("RECOGNITION_TEST_BAD_SECRETS", &mut secrets).is_err());
+    }
+}
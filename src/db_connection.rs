@@ -1,12 +1,41 @@
-use std::env;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::{PgConnection, QueryResult};
 
-use diesel::{Connection, PgConnection};
-use dotenvy::dotenv;
+use crate::config::DatabaseConfig;
+use crate::errors::DbError;
 
-pub fn connection() -> PgConnection {
-    dotenv().ok();
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+pub fn create_pool(config: &DatabaseConfig) -> DbPool {
+    Pool::builder()
+        .max_size(config.pool_size)
+        .build(ConnectionManager::new(&config.url))
+        .unwrap_or_else(|_| panic!("Error connecting to {}", config.url))
+}
+
+/// Выполняет блокирующие запросы Diesel на соединении из пула вне потоков
+/// исполнителя tokio
+pub async fn interact<T, F>(pool: &DbPool, f: F) -> Result<T, DbError>
+where
+    F: FnOnce(&mut PgConnection) -> QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    interact_with(pool, move |conn| Ok(f(conn)?)).await
+}
+
+/// То же, что [`interact`], но с собственным типом ошибки замыкания
+pub async fn interact_with<T, E, F>(pool: &DbPool, f: F) -> Result<T, E>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<DbError> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(DbError::from)?;
+        f(&mut conn)
+    })
+    .await
+    .map_err(DbError::from)?
 }
@@ -3,17 +3,35 @@ pub enum CreatePhotoError {
     #[error("ORM request error {0}")]
     DieselError(#[from] diesel::result::Error),
 
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+
     #[error("Tokio StdIO error: {0}")]
     TokioStdIO(#[from] tokio::io::Error),
 
     #[error("Image error: {0}")]
     ImageError(#[from] image::ImageError),
 
-    #[error("SerdeJson error: {0}")]
-    SerdeJson(#[from] serde_json::Error),
+    #[error("ML backend error: {0}")]
+    Ml(#[from] MlError),
 
-    #[error("Reqwest error: {0}")]
-    Reqwest(#[from] reqwest::Error),
+    #[error("Blocking task error: {0}")]
+    Blocking(#[from] tokio::task::JoinError),
+
+    #[error("Job {0} has no photo")]
+    JobWithoutPhoto(i32),
+
+    #[error("Photo {0} has no original file")]
+    MissingOriginal(i32),
+
+    #[error("The same file is already uploaded as photo {0}")]
+    Duplicate(i32),
+
+    #[error(transparent)]
+    Album(#[from] AlbumError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
 
     #[error("unknown data store error")]
     Unknown,
@@ -21,3 +39,252 @@ pub enum CreatePhotoError {
     // #[error("")]
     // Infallible(#[from] std::convert::Infallible),
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum MlError {
+    #[error("Reqwest error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+
+    #[error("SerdeJson error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageError {
+    #[error("Storage IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Storage request error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Storage responded {status} for {key}")]
+    Status {
+        key: String,
+        status: reqwest::StatusCode,
+    },
+
+    #[error("Invalid storage endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("File {0} not found in storage")]
+    NotFound(String),
+
+    #[error("Range is not satisfiable for file of {0} bytes")]
+    InvalidRange(u64),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DbError {
+    #[error("ORM request error {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] diesel::r2d2::PoolError),
+
+    #[error("Blocking task error: {0}")]
+    Blocking(#[from] tokio::task::JoinError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PhotosSearchError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Ml(#[from] MlError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AlbumError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("ORM request error {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    Ml(#[from] MlError),
+
+    #[error("Photos of a smart album are chosen by its rule")]
+    SmartAlbum,
+
+    #[error("Invalid album rule: {0}")]
+    InvalidRule(&'static str),
+
+    #[error("Not enough permissions for the album")]
+    Forbidden,
+
+    #[error("Album cannot be shared with its owner")]
+    SelfShare,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ShareLinkError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("ORM request error {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error("Password hashing error: {0}")]
+    Bcrypt(#[from] bcrypt::BcryptError),
+
+    #[error("Blocking task error: {0}")]
+    Blocking(#[from] tokio::task::JoinError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("Share link must point to either an album or a photo")]
+    InvalidTarget,
+
+    #[error("Share link expiry must be in the future")]
+    InvalidExpiry,
+
+    #[error("Share link has expired")]
+    Expired,
+
+    #[error("Share link password is required")]
+    PasswordRequired,
+
+    #[error("Wrong share link password")]
+    WrongPassword,
+
+    #[error("Too many wrong share link passwords, try again later")]
+    TooManyAttempts,
+
+    #[error("Downloads are not allowed for this share link")]
+    DownloadForbidden,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MediaError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("Invalid or expired media signature")]
+    InvalidSignature,
+
+    #[error("Media file not found")]
+    NotFound,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("ORM request error {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error("Password hashing error: {0}")]
+    Bcrypt(#[from] bcrypt::BcryptError),
+
+    #[error("Blocking task error: {0}")]
+    Blocking(#[from] tokio::task::JoinError),
+
+    #[error("Token encoding error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+
+    #[error("Wrong email or password")]
+    InvalidCredentials,
+
+    #[error("Refresh token not found")]
+    MissingRefreshToken,
+
+    #[error("Refresh token is invalid or expired")]
+    InvalidRefreshToken,
+
+    #[error("Refresh token was already used, the session is revoked")]
+    RefreshTokenReused,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("ORM request error {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error("Password hashing error: {0}")]
+    Bcrypt(#[from] bcrypt::BcryptError),
+
+    #[error("Blocking task error: {0}")]
+    Blocking(#[from] tokio::task::JoinError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("Image error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Current password is wrong")]
+    WrongPassword,
+
+    #[error("Username must not be empty or longer than 50 characters")]
+    InvalidUsername,
+
+    #[error("Email must be a valid address not longer than 50 characters")]
+    InvalidEmail,
+
+    #[error("Password must be at least 8 characters long")]
+    WeakPassword,
+
+    #[error("Username is already taken")]
+    UsernameTaken,
+
+    #[error("Email is already registered")]
+    EmailTaken,
+
+    #[error("Self-registration is disabled")]
+    RegistrationDisabled,
+
+    #[error("Invite code is invalid, expired, already used or issued for another email")]
+    InvalidInvite,
+
+    #[error("Invite expiry must be in the future")]
+    InvalidExpiry,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiKeyError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("API key name must not be empty or longer than 100 characters")]
+    InvalidName,
+
+    #[error("API key expiry must be in the future")]
+    InvalidExpiry,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PageError {
+    #[error("Invalid cursor")]
+    InvalidCursor,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Error reading config {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Error parsing config {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid value of environment variable {name}: {value}")]
+    Env { name: &'static str, value: String },
+
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+}
@@ -1,19 +1,78 @@
+use std::sync::Arc;
+
+use config::{Config, MlBackendKind, StorageBackendKind};
+use ml::{FakeMlBackend, HttpMlBackend, HttpMlConfig, MlBackend};
 use routes::craete_app;
+use state::AppState;
+use storage::{LocalStorage, S3Storage, Storage};
 
+pub mod config;
 pub mod db_connection;
 pub mod errors;
 pub mod middleware;
+pub mod ml;
 pub mod models;
 pub mod routes;
 pub mod schema;
 pub mod services;
+pub mod state;
+pub mod storage;
+pub mod worker;
+
+#[cfg(test)]
+mod test_utils;
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
+    dotenvy::dotenv().ok();
+
+    let config = Config::load().unwrap_or_else(|err| panic!("{err}"));
+
+    let ml: Arc<dyn MlBackend> = match config.ml.backend {
+        MlBackendKind::Fake => Arc::new(FakeMlBackend::new()),
+        MlBackendKind::Http => Arc::new(
+            HttpMlBackend::new(HttpMlConfig::from(&config.ml)).expect("Error creating ML client"),
+        ),
+    };
+
+    let storage: Arc<dyn Storage> = match config.storage.backend {
+        StorageBackendKind::Local => {
+            for dir in config.storage.dirs() {
+                std::fs::create_dir_all(dir).expect("Error creating storage directory");
+            }
+            Arc::new(LocalStorage::new())
+        }
+        StorageBackendKind::S3 => {
+            Arc::new(S3Storage::new(&config.s3).expect("Error creating S3 storage client"))
+        }
+    };
+
+    let state = AppState {
+        pool: db_connection::create_pool(&config.database),
+        config: Arc::new(config),
+        ml,
+        storage,
+    };
+
+    // `recognition backfill-renditions [--all]` ставит задачи на создание
+    // миниатюр и превью и завершается, обработают их запущенные серверы
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("backfill-renditions") {
+        let all = args.iter().any(|arg| arg == "--all");
+        let queued = services::jobs::enqueue_renditions_backfill(&state.pool, all)
+            .await
+            .expect("Error queueing renditions backfill");
+        println!("Queued {queued} renditions jobs");
+        return;
+    }
+
+    worker::spawn_workers(state.clone());
 
-    let app = craete_app().await;
+    let listener = tokio::net::TcpListener::bind(&state.config.server.bind_address)
+        .await
+        .unwrap();
+    let app = craete_app(state).await;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
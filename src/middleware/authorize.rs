@@ -1,22 +1,39 @@
 use axum::{
     body::Body,
-    extract::Request,
-    http::{Response, StatusCode},
+    extract::{OriginalUri, Request, State},
+    http::{header, HeaderMap, Method, Response, StatusCode},
     middleware::Next,
 };
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::{
+    decode, decode_header, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header,
+    TokenData, Validation,
+};
 use serde::{Deserialize, Serialize};
 use tower_cookies::Cookies;
 
-use crate::{middleware::errors::Error, services::users::get_user_by_email};
+use crate::{
+    config::AuthConfig,
+    middleware::errors::Error,
+    models::{ApiKeyScope, User},
+    services::{
+        api_keys::{authenticate_api_key, API_KEY_PREFIX},
+        auth::get_session_user,
+    },
+    state::AppState,
+};
+
+/// Cookie с токеном доступа
+pub const TOKEN_COOKIE: &str = "token";
 
 #[derive(Serialize, Deserialize)]
 pub struct Cliams {
     pub exp: usize,
     pub iat: usize,
     pub email: String,
+    /// Сессия, выданная при входе, с её отзывом токен перестаёт действовать
+    pub sid: String,
 }
 
 pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::BcryptError> {
@@ -28,48 +45,173 @@ pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
     Ok(hash)
 }
 
-pub fn encode_jwt(email: String) -> Result<String, StatusCode> {
-    let jwt_token: String = "randomstring".to_string();
-
+/// Токен доступа сессии `sid`, подписанный текущим секретом с его `kid`
+pub fn encode_jwt(
+    email: String,
+    sid: String,
+    config: &AuthConfig,
+) -> Result<String, jsonwebtoken::errors::Error> {
     let now = Utc::now();
-    let expire: chrono::TimeDelta = Duration::hours(24);
+    let expire: chrono::TimeDelta = Duration::seconds(config.token_lifetime_secs);
     let exp: usize = (now + expire).timestamp() as usize;
     let iat: usize = now.timestamp() as usize;
 
-    let claim = Cliams { iat, exp, email };
-    let secret = jwt_token.clone();
+    let claim = Cliams {
+        iat,
+        exp,
+        email,
+        sid,
+    };
+    let header = Header {
+        kid: Some(config.jwt_key_id.clone()),
+        ..Header::new(Algorithm::HS256)
+    };
 
     encode(
-        &Header::default(),
+        &header,
         &claim,
-        &EncodingKey::from_secret(secret.as_ref()),
+        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
     )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-pub fn decode_jwt(jwt: String) -> Result<TokenData<Cliams>, StatusCode> {
-    let secret = "randomstring".to_string();
+/// Проверяет токен секретом по его `kid`: текущим или одним из прежних
+pub fn decode_jwt(
+    jwt: &str,
+    config: &AuthConfig,
+) -> Result<TokenData<Cliams>, jsonwebtoken::errors::Error> {
+    decode_jwt_with(jwt, config, Validation::new(Algorithm::HS256))
+}
 
-    let result: Result<TokenData<Cliams>, StatusCode> = decode(
-        &jwt,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
-    result
+/// Сессия токена доступа, в том числе уже истёкшего: для выхода достаточно
+/// верной подписи
+pub fn token_session_id(jwt: &str, config: &AuthConfig) -> Option<String> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.validate_exp = false;
+    Some(decode_jwt_with(jwt, config, validation).ok()?.claims.sid)
+}
+
+fn decode_jwt_with(
+    jwt: &str,
+    config: &AuthConfig,
+    validation: Validation,
+) -> Result<TokenData<Cliams>, jsonwebtoken::errors::Error> {
+    let kid = decode_header(jwt)?.kid.ok_or(ErrorKind::InvalidToken)?;
+    let secret = match kid == config.jwt_key_id {
+        true => &config.jwt_secret,
+        false => config
+            .previous_jwt_secrets
+            .0
+            .get(&kid)
+            .ok_or(ErrorKind::InvalidToken)?,
+    };
+
+    decode(jwt, &DecodingKey::from_secret(secret.as_ref()), &validation)
+}
+
+/// Чем подтверждён запрос
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Credentials {
+    /// Токен доступа сессии
+    Session { session_id: String },
+    /// Персональный API ключ
+    ApiKey { id: i32, scope: ApiKeyScope },
+}
+
+impl Credentials {
+    /// Сессия запроса, у запросов по API ключу её нет
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Credentials::Session { session_id } => Some(session_id),
+            Credentials::ApiKey { .. } => None,
+        }
+    }
+}
+
+/// Токен из заголовка `Authorization: Bearer`
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then_some(token.trim())
+}
+
+/// Права, которые нужны API ключу для запроса. `None` - маршрут доступен
+/// только из сессии, например управление самими ключами.
+fn required_scope(method: &Method, path: &str) -> Option<ApiKeyScope> {
+    let path = path.trim_end_matches('/');
+    if path.starts_with("/api/settings") {
+        return None;
+    }
+
+    match (method, path) {
+        (&Method::GET | &Method::HEAD | &Method::OPTIONS, _) => Some(ApiKeyScope::Read),
+        // Поиск по образцу принимает изображение, но ничего не меняет
+        (&Method::POST, "/api/photo/similar" | "/api/person/search") => Some(ApiKeyScope::Read),
+        (&Method::POST, "/api/photo") => Some(ApiKeyScope::Upload),
+        _ => Some(ApiKeyScope::Write),
+    }
 }
 
 pub async fn authorize(
+    State(state): State<AppState>,
     cookies: Cookies,
     mut req: Request,
     next: Next,
 ) -> Result<Response<Body>, Error> {
-    let token = match cookies.get("token") {
-        Some(token) => token.value().to_string(),
-        None => return Err(Error::new("Token not found", StatusCode::UNAUTHORIZED)),
+    let (current_user, credentials) = authenticate(&state, &cookies, req.headers()).await?;
+
+    if let Credentials::ApiKey { scope, .. } = &credentials {
+        let path = match req.extensions().get::<OriginalUri>() {
+            Some(uri) => uri.path(),
+            None => req.uri().path(),
+        };
+        match required_scope(req.method(), path) {
+            Some(required) if *scope >= required => {}
+            Some(_) => {
+                return Err(Error::new(
+                    "API key scope does not allow this request",
+                    StatusCode::FORBIDDEN,
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    "API keys are not allowed for this request",
+                    StatusCode::FORBIDDEN,
+                ))
+            }
+        }
+    }
+
+    req.extensions_mut().insert(current_user);
+    req.extensions_mut().insert(credentials);
+    Ok(next.run(req).await)
+}
+
+/// Пользователь по токену из заголовка `Authorization: Bearer` или из cookie.
+/// В заголовке может быть и токен доступа, и персональный API ключ.
+pub async fn authenticate(
+    state: &AppState,
+    cookies: &Cookies,
+    headers: &HeaderMap,
+) -> Result<(User, Credentials), Error> {
+    let token = match (bearer_token(headers), cookies.get(TOKEN_COOKIE)) {
+        (Some(token), _) => token.to_string(),
+        (None, Some(cookie)) => cookie.value().to_string(),
+        (None, None) => return Err(Error::new("Token not found", StatusCode::UNAUTHORIZED)),
     };
 
-    let token_data = match decode_jwt(token) {
+    if token.starts_with(API_KEY_PREFIX) {
+        return match authenticate_api_key(&state.pool, &token).await? {
+            Some((user, id, scope)) => Ok((user, Credentials::ApiKey { id, scope })),
+            None => Err(Error::new(
+                "API key is invalid or expired",
+                StatusCode::UNAUTHORIZED,
+            )),
+        };
+    }
+
+    let token_data = match decode_jwt(&token, &state.config.auth) {
         Ok(data) => data,
         Err(_) => {
             return Err(Error::new(
@@ -79,8 +221,96 @@ pub async fn authorize(
         }
     };
 
-    let current_user = get_user_by_email(&token_data.claims.email).await.unwrap();
+    let claims = token_data.claims;
+    match get_session_user(&state.pool, claims.email, claims.sid.clone()).await? {
+        Some(user) => Ok((
+            user,
+            Credentials::Session {
+                session_id: claims.sid,
+            },
+        )),
+        None => Err(Error::new(
+            "User or session not found",
+            StatusCode::UNAUTHORIZED,
+        )),
+    }
+}
 
-    req.extensions_mut().insert(current_user);
-    Ok(next.run(req).await)
+/// Пользователь запроса, для маршрутов с необязательной авторизацией
+pub async fn current_user(
+    state: &AppState,
+    cookies: &Cookies,
+    headers: &HeaderMap,
+) -> Result<User, Error> {
+    Ok(authenticate(state, cookies, headers).await?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_need_read_scope() {
+        for method in [Method::GET, Method::HEAD, Method::OPTIONS] {
+            assert_eq!(
+                required_scope(&method, "/api/photo"),
+                Some(ApiKeyScope::Read)
+            );
+        }
+        assert_eq!(
+            required_scope(&Method::POST, "/api/photo/similar"),
+            Some(ApiKeyScope::Read)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/api/person/search/"),
+            Some(ApiKeyScope::Read)
+        );
+    }
+
+    #[test]
+    fn only_photo_upload_needs_upload_scope() {
+        assert_eq!(
+            required_scope(&Method::POST, "/api/photo"),
+            Some(ApiKeyScope::Upload)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/api/album"),
+            Some(ApiKeyScope::Write)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/photo/1"),
+            Some(ApiKeyScope::Write)
+        );
+        assert_eq!(
+            required_scope(&Method::PUT, "/api/photo/1"),
+            Some(ApiKeyScope::Write)
+        );
+    }
+
+    #[test]
+    fn settings_are_closed_to_api_keys() {
+        for method in [Method::GET, Method::POST, Method::DELETE] {
+            assert_eq!(required_scope(&method, "/api/settings/api-keys"), None);
+        }
+        assert_eq!(required_scope(&Method::GET, "/api/settings"), None);
+    }
+
+    #[test]
+    fn wider_scopes_include_narrower_ones() {
+        assert!(ApiKeyScope::Read < ApiKeyScope::Upload);
+        assert!(ApiKeyScope::Upload < ApiKeyScope::Write);
+    }
+
+    #[test]
+    fn bearer_token_is_read_from_authorization_header() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(bearer_token(&headers("Bearer abc")), Some("abc"));
+        assert_eq!(bearer_token(&headers("bearer  abc ")), Some("abc"));
+        assert_eq!(bearer_token(&headers("Basic abc")), None);
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
 }
@@ -6,6 +6,11 @@ use axum::{
 };
 use serde_json::json;
 
+use crate::errors::{
+    AlbumError, ApiKeyError, AuthError, CreatePhotoError, DbError, MediaError, MlError, PageError,
+    PhotosSearchError, ShareLinkError, StorageError, UserError,
+};
+
 pub struct Error {
     pub message: String,
     pub status_code: StatusCode,
@@ -29,3 +34,203 @@ impl IntoResponse for Error {
         (self.status_code, body).into_response()
     }
 }
+
+impl From<diesel::result::Error> for Error {
+    fn from(err: diesel::result::Error) -> Self {
+        match err {
+            diesel::result::Error::NotFound => Error::new("Not found", StatusCode::NOT_FOUND),
+            err => {
+                log::error!("Database error: {err}");
+                Error::new("Database error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+impl From<DbError> for Error {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::Diesel(err) => err.into(),
+            err => {
+                log::error!("Database error: {err}");
+                Error::new("Database unavailable", StatusCode::SERVICE_UNAVAILABLE)
+            }
+        }
+    }
+}
+
+impl From<MlError> for Error {
+    fn from(err: MlError) -> Self {
+        log::error!("ML backend error: {err}");
+        Error::new("ML backend error", StatusCode::BAD_GATEWAY)
+    }
+}
+
+impl From<AuthError> for Error {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Db(err) => err.into(),
+            AuthError::Diesel(err) => err.into(),
+            err @ (AuthError::InvalidCredentials
+            | AuthError::MissingRefreshToken
+            | AuthError::InvalidRefreshToken
+            | AuthError::RefreshTokenReused) => {
+                Error::new(&err.to_string(), StatusCode::UNAUTHORIZED)
+            }
+            err => {
+                log::error!("Auth error: {err}");
+                Error::new("Auth error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+impl From<UserError> for Error {
+    fn from(err: UserError) -> Self {
+        match err {
+            UserError::Db(err) => err.into(),
+            UserError::Diesel(err) => err.into(),
+            UserError::Storage(err) => err.into(),
+            UserError::Image(err) => Error::new(
+                &format!("Unsupported image: {err}"),
+                StatusCode::BAD_REQUEST,
+            ),
+            err @ (UserError::InvalidUsername
+            | UserError::InvalidEmail
+            | UserError::WeakPassword
+            | UserError::InvalidInvite
+            | UserError::InvalidExpiry) => Error::new(&err.to_string(), StatusCode::BAD_REQUEST),
+            err @ (UserError::WrongPassword | UserError::RegistrationDisabled) => {
+                Error::new(&err.to_string(), StatusCode::FORBIDDEN)
+            }
+            err @ (UserError::UsernameTaken | UserError::EmailTaken) => {
+                Error::new(&err.to_string(), StatusCode::CONFLICT)
+            }
+            err => {
+                log::error!("User error: {err}");
+                Error::new("User error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+impl From<ApiKeyError> for Error {
+    fn from(err: ApiKeyError) -> Self {
+        match err {
+            ApiKeyError::Db(err) => err.into(),
+            err => Error::new(&err.to_string(), StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
+impl From<PageError> for Error {
+    fn from(err: PageError) -> Self {
+        Error::new(&err.to_string(), StatusCode::BAD_REQUEST)
+    }
+}
+
+impl From<AlbumError> for Error {
+    fn from(err: AlbumError) -> Self {
+        match err {
+            AlbumError::Db(err) => err.into(),
+            AlbumError::Diesel(err) => err.into(),
+            AlbumError::Ml(err) => err.into(),
+            err @ AlbumError::SmartAlbum => Error::new(&err.to_string(), StatusCode::CONFLICT),
+            err @ (AlbumError::InvalidRule(_) | AlbumError::SelfShare) => {
+                Error::new(&err.to_string(), StatusCode::BAD_REQUEST)
+            }
+            err @ AlbumError::Forbidden => Error::new(&err.to_string(), StatusCode::FORBIDDEN),
+        }
+    }
+}
+
+impl From<ShareLinkError> for Error {
+    fn from(err: ShareLinkError) -> Self {
+        match err {
+            ShareLinkError::Db(err) => err.into(),
+            ShareLinkError::Diesel(err) => err.into(),
+            ShareLinkError::Storage(err) => err.into(),
+            err @ (ShareLinkError::InvalidTarget | ShareLinkError::InvalidExpiry) => {
+                Error::new(&err.to_string(), StatusCode::BAD_REQUEST)
+            }
+            err @ (ShareLinkError::PasswordRequired | ShareLinkError::WrongPassword) => {
+                Error::new(&err.to_string(), StatusCode::UNAUTHORIZED)
+            }
+            err @ ShareLinkError::DownloadForbidden => {
+                Error::new(&err.to_string(), StatusCode::FORBIDDEN)
+            }
+            err @ ShareLinkError::Expired => Error::new(&err.to_string(), StatusCode::GONE),
+            err @ ShareLinkError::TooManyAttempts => {
+                Error::new(&err.to_string(), StatusCode::TOO_MANY_REQUESTS)
+            }
+            err => {
+                log::error!("Share link error: {err}");
+                Error::new("Share link error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+impl From<MediaError> for Error {
+    fn from(err: MediaError) -> Self {
+        match err {
+            MediaError::Db(err) => err.into(),
+            MediaError::Storage(err) => err.into(),
+            err @ MediaError::InvalidSignature => {
+                Error::new(&err.to_string(), StatusCode::FORBIDDEN)
+            }
+            err @ MediaError::NotFound => Error::new(&err.to_string(), StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::NotFound(_) => Error::new("Media file not found", StatusCode::NOT_FOUND),
+            err @ StorageError::InvalidRange(_) => {
+                Error::new(&err.to_string(), StatusCode::RANGE_NOT_SATISFIABLE)
+            }
+            err @ (StorageError::Http(_) | StorageError::Status { .. }) => {
+                log::error!("Storage backend error: {err}");
+                Error::new("Storage backend error", StatusCode::BAD_GATEWAY)
+            }
+            err => {
+                log::error!("Storage error: {err}");
+                Error::new("Storage error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+impl From<PhotosSearchError> for Error {
+    fn from(err: PhotosSearchError) -> Self {
+        match err {
+            PhotosSearchError::Db(err) => err.into(),
+            PhotosSearchError::Ml(err) => err.into(),
+        }
+    }
+}
+
+impl From<CreatePhotoError> for Error {
+    fn from(err: CreatePhotoError) -> Self {
+        match err {
+            CreatePhotoError::ImageError(err) => Error::new(
+                &format!("Unsupported image: {err}"),
+                StatusCode::BAD_REQUEST,
+            ),
+            err @ CreatePhotoError::Duplicate(_) => {
+                Error::new(&err.to_string(), StatusCode::CONFLICT)
+            }
+            CreatePhotoError::DieselError(err) => err.into(),
+            CreatePhotoError::Db(err) => err.into(),
+            CreatePhotoError::Ml(err) => err.into(),
+            CreatePhotoError::Album(err) => err.into(),
+            CreatePhotoError::Storage(err) => err.into(),
+            err => {
+                log::error!("Photo error: {err}");
+                Error::new("Photo processing error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use async_trait::async_trait;
+
+use crate::errors::MlError;
+use crate::ml::{MlBackend, RecognizedFaceOutput, EMBEDDING_DIM};
+
+/// Детерминированная реализация ML сервиса без сетевых вызовов.
+///
+/// Одинаковые входные данные дают одинаковые эмбеддинги, поэтому загрузка и
+/// поиск фотографий работают без запущенного Python сервиса.
+#[derive(Debug, Clone, Default)]
+pub struct FakeMlBackend {
+    faces: Option<Vec<RecognizedFaceOutput>>,
+}
+
+impl FakeMlBackend {
+    pub fn new() -> Self {
+        FakeMlBackend::default()
+    }
+
+    /// Возвращать заданный список лиц для любого изображения
+    pub fn with_faces(faces: Vec<RecognizedFaceOutput>) -> Self {
+        FakeMlBackend { faces: Some(faces) }
+    }
+
+    pub fn embedding(seed: impl Hash) -> Vec<f32> {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        let mut state = hasher.finish();
+
+        let mut embedding: Vec<f32> = (0..EMBEDDING_DIM)
+            .map(|_| {
+                // splitmix64
+                state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                (z as f64 / u64::MAX as f64 * 2.0 - 1.0) as f32
+            })
+            .collect();
+
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+}
+
+#[async_trait]
+impl MlBackend for FakeMlBackend {
+    async fn faces_recognition(
+        &self,
+        image: Vec<u8>,
+    ) -> Result<Vec<RecognizedFaceOutput>, MlError> {
+        if let Some(faces) = &self.faces {
+            return Ok(faces.clone());
+        }
+
+        // Одно "лицо" в центре изображения
+        let (width, height) = match image::load_from_memory(&image) {
+            Ok(img) => (img.width() as f32, img.height() as f32),
+            Err(_) => return Ok(vec![]),
+        };
+        let bbox = [
+            width / 4.0,
+            height / 4.0,
+            width * 3.0 / 4.0,
+            height * 3.0 / 4.0,
+        ];
+        let (cx, cy) = (width / 2.0, height / 2.0);
+
+        Ok(vec![RecognizedFaceOutput {
+            score: 1.0,
+            bbox,
+            landmarks: [(cx, cy); 5],
+            embedding: Self::embedding(("face", &image)),
+        }])
+    }
+
+    async fn clip_textual(&self, text: &str) -> Result<Vec<f32>, MlError> {
+        Ok(Self::embedding(("text", text)))
+    }
+
+    async fn clip_visual(&self, image: Vec<u8>) -> Result<Vec<f32>, MlError> {
+        Ok(Self::embedding(("image", &image)))
+    }
+}
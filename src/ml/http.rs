@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::{multipart, Client};
+
+use crate::config::MlConfig;
+use crate::errors::MlError;
+use crate::ml::{MlBackend, RecognizedFaceOutput};
+
+#[derive(Debug, Clone)]
+pub struct HttpMlConfig {
+    /// Адрес ML сервиса, например `http://0.0.0.0:3003`
+    pub base_url: String,
+    /// Таймаут запроса целиком
+    pub timeout: Duration,
+    /// Таймаут установки соединения
+    pub connect_timeout: Duration,
+}
+
+impl From<&MlConfig> for HttpMlConfig {
+    fn from(config: &MlConfig) -> Self {
+        HttpMlConfig {
+            base_url: config.url.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+            connect_timeout: Duration::from_secs(config.connect_timeout_secs),
+        }
+    }
+}
+
+/// Клиент Python сервиса распознавания, общий для всех запросов
+#[derive(Debug, Clone)]
+pub struct HttpMlBackend {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpMlBackend {
+    pub fn new(config: HttpMlConfig) -> Result<Self, MlError> {
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()?;
+
+        Ok(HttpMlBackend {
+            client,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/{endpoint}", self.base_url)
+    }
+
+    fn image_form(image: Vec<u8>) -> Result<multipart::Form, MlError> {
+        let part = multipart::Part::bytes(image)
+            .file_name("image.jpeg")
+            .mime_str("image/jpeg")?;
+        Ok(multipart::Form::new().part("image", part))
+    }
+}
+
+#[async_trait]
+impl MlBackend for HttpMlBackend {
+    async fn faces_recognition(
+        &self,
+        image: Vec<u8>,
+    ) -> Result<Vec<RecognizedFaceOutput>, MlError> {
+        let response_body = self
+            .client
+            .post(self.url("recognition-faces"))
+            .multipart(Self::image_form(image)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(serde_json::from_str(&response_body)?)
+    }
+
+    async fn clip_textual(&self, text: &str) -> Result<Vec<f32>, MlError> {
+        let response_body = self
+            .client
+            .post(self.url("clip-textual"))
+            .query(&[("text", text)])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(serde_json::from_str(&response_body)?)
+    }
+
+    async fn clip_visual(&self, image: Vec<u8>) -> Result<Vec<f32>, MlError> {
+        let response_body = self
+            .client
+            .post(self.url("clip-visual"))
+            .multipart(Self::image_form(image)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(serde_json::from_str(&response_body)?)
+    }
+}
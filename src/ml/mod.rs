@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::MlError;
+
+pub mod fake;
+pub mod http;
+
+pub use fake::FakeMlBackend;
+pub use http::{HttpMlBackend, HttpMlConfig};
+
+/// Размерность эмбеддингов CLIP и лиц, совпадает с `VECTOR(512)` в схеме
+pub const EMBEDDING_DIM: usize = 512;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RecognizedFaceOutput {
+    pub score: f32,
+    pub bbox: [f32; 4],
+    pub landmarks: [(f32, f32); 5],
+    pub embedding: Vec<f32>,
+}
+
+/// Операции ML сервиса, используемые при загрузке и поиске фотографий
+#[async_trait]
+pub trait MlBackend: Send + Sync {
+    /// Поиск лиц на изображении
+    async fn faces_recognition(&self, image: Vec<u8>)
+        -> Result<Vec<RecognizedFaceOutput>, MlError>;
+
+    /// Эмбеддинг CLIP для текстового запроса
+    async fn clip_textual(&self, text: &str) -> Result<Vec<f32>, MlError>;
+
+    /// Эмбеддинг CLIP для изображения
+    async fn clip_visual(&self, image: Vec<u8>) -> Result<Vec<f32>, MlError>;
+}
@@ -1,10 +1,90 @@
 use axum::body::Bytes;
 use axum_typed_multipart::{FieldData, TryFromMultipart};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use pgvector::Vector;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::config::AuthConfig;
+
+/// Перечисление, хранящееся в колонке VARCHAR/TEXT в виде строки
+macro_rules! text_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident => $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(
+            diesel::AsExpression,
+            diesel::FromSqlRow,
+            Serialize,
+            Deserialize,
+            ToSchema,
+            Clone,
+            Copy,
+            Debug,
+            PartialEq,
+            Eq,
+        )]
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        pub enum $name {
+            $(#[serde(rename = $value)] $variant),+
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $value),+
+                }
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+            ) -> diesel::serialize::Result {
+                use std::io::Write;
+                out.write_all(self.as_str().as_bytes())?;
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+            fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+                match bytes.as_bytes() {
+                    $(v if v == $value.as_bytes() => Ok($name::$variant),)+
+                    other => Err(format!(
+                        "Unknown {} value: {}",
+                        stringify!($name),
+                        String::from_utf8_lossy(other)
+                    )
+                    .into()),
+                }
+            }
+        }
+    };
+}
+
+/// Поле сортировки списка, передаётся в параметре `sort`
+macro_rules! sort_field {
+    ($(#[$meta:meta])* pub enum $name:ident { $(#[default] $default:ident => $default_value:literal,)? $($variant:ident => $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub enum $name {
+            $(#[default] #[serde(rename = $default_value)] $default,)?
+            $(#[serde(rename = $value)] $variant),*
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$default => $default_value,)?
+                    $($name::$variant => $value),*
+                }
+            }
+        }
+    };
+}
+
 #[derive(Queryable, Selectable, Serialize, Deserialize, ToSchema, Clone, Debug)]
 #[diesel(table_name = crate::schema::users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -15,8 +95,11 @@ pub struct User {
     pub username: String,
     /// Почта пользователя
     pub email: String,
-    /// Пароль пользователя
+    /// Хэш пароля пользователя, в ответах API не отдаётся
+    #[serde(skip_serializing)]
     pub password: String,
+    /// Ключ файла аватара, сам файл отдаётся через `/api/media/avatar/{user_id}`
+    pub avatar: Option<String>,
     /// Возможности администратора
     pub is_admin: bool,
 }
@@ -46,9 +129,15 @@ pub struct Photo {
     pub title: Option<String>,
     /// Id пользователя, загрузившего изображение
     pub user_id: i32,
-    /// Id альбома
-    pub album_id: Option<i32>,
     pub embedding: Option<Vector>,
+    /// Путь к исходному загруженному файлу
+    pub original_path: Option<String>,
+    /// MIME тип исходного файла
+    pub mime_type: Option<String>,
+    #[diesel(embed)]
+    pub exif: PhotoExif,
+    #[diesel(embed)]
+    pub renditions: PhotoRenditions,
 }
 
 #[derive(Queryable, Serialize, Deserialize, Selectable, ToSchema, Clone, Debug)]
@@ -57,14 +146,116 @@ pub struct Photo {
 pub struct ListPhoto {
     /// Id изображения
     pub id: i32,
-    /// Путь к файлу изображения
+    /// Путь к JPEG для показа
     pub path: Option<String>,
     /// Наименование изображения
     pub title: Option<String>,
     /// Id пользователя, загрузившего изображение
     pub user_id: i32,
-    /// Id альбома
-    pub album_id: Option<i32>,
+    /// Путь к исходному загруженному файлу
+    pub original_path: Option<String>,
+    /// MIME тип исходного файла
+    pub mime_type: Option<String>,
+    /// Метаданные EXIF исходного файла
+    #[diesel(embed)]
+    pub exif: PhotoExif,
+    /// Уменьшенные копии для сетки и просмотра
+    #[diesel(embed)]
+    pub renditions: PhotoRenditions,
+}
+
+/// Ключи уменьшенных копий изображения в хранилище файлов. Пустые,
+/// пока изображение не обработано.
+#[derive(
+    Queryable,
+    Selectable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+)]
+#[diesel(table_name = crate::schema::photos)]
+#[diesel(check_for_backend(diesel::pg::Pg), treat_none_as_null = true)]
+pub struct PhotoRenditions {
+    /// Квадратная миниатюра в JPEG
+    pub thumbnail_path: Option<String>,
+    /// Превью для просмотра в JPEG
+    pub preview_path: Option<String>,
+    /// Квадратная миниатюра в WebP, если включена
+    pub thumbnail_webp_path: Option<String>,
+    /// Превью для просмотра в WebP, если включено
+    pub preview_webp_path: Option<String>,
+}
+
+/// Метаданные EXIF, извлечённые из загруженного файла
+#[derive(
+    Queryable,
+    Selectable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+)]
+#[diesel(table_name = crate::schema::photos)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PhotoExif {
+    /// Дата и время съёмки по часам камеры
+    pub taken_at: Option<NaiveDateTime>,
+    /// Производитель камеры
+    pub camera_make: Option<String>,
+    /// Модель камеры
+    pub camera_model: Option<String>,
+    /// Модель объектива
+    pub lens_model: Option<String>,
+    /// Выдержка в секундах
+    pub exposure_time: Option<f64>,
+    /// Диафрагменное число
+    pub f_number: Option<f64>,
+    /// Светочувствительность ISO
+    pub iso: Option<i32>,
+    /// Фокусное расстояние в миллиметрах
+    pub focal_length: Option<f64>,
+    /// Ориентация изображения по EXIF, от 1 до 8
+    pub orientation: Option<i16>,
+    /// Широта в градусах, южная - отрицательная
+    pub latitude: Option<f64>,
+    /// Долгота в градусах, западная - отрицательная
+    pub longitude: Option<f64>,
+}
+
+/// Чьи фотографии, альбомы и личности доступны в запросе
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    /// Данные всех пользователей
+    Any,
+    /// Только данные пользователя с указанным id
+    User(i32),
+}
+
+impl Owner {
+    pub fn of(user: &User, config: &AuthConfig) -> Self {
+        if user.is_admin && config.admins_see_all {
+            Owner::Any
+        } else {
+            Owner::User(user.id)
+        }
+    }
+
+    /// Id пользователя, которым ограничен запрос, `None` - без ограничения
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Owner::Any => None,
+            Owner::User(user_id) => Some(*user_id),
+        }
+    }
 }
 
 #[derive(Insertable, ToSchema, Clone, Debug, Default)]
@@ -74,16 +265,16 @@ pub struct NewPhoto {
     pub title: Option<String>,
     /// Id пользователя, загрузившего изображение
     pub user_id: i32,
-    /// Id альбома
-    pub album_id: Option<i32>,
+    /// SHA-256 исходного файла
+    pub content_hash: Option<String>,
 }
 
 impl NewPhoto {
-    pub fn from_form(photo: &PhotoForm, uid: i32) -> Self {
+    pub fn from_form(photo: &PhotoForm, uid: i32, content_hash: String) -> Self {
         NewPhoto {
             title: photo.title.clone(),
             user_id: uid,
-            album_id: photo.album_id,
+            content_hash: Some(content_hash),
         }
     }
 }
@@ -96,6 +287,33 @@ pub struct Album {
     pub id: i32,
     /// Наименование альбома
     pub title: String,
+    /// Id владельца альбома
+    pub user_id: i32,
+    /// Id фотографии обложки
+    pub cover_photo_id: Option<i32>,
+    /// Количество фотографий в альбоме
+    #[diesel(
+        select_expression = diesel::dsl::sql(ALBUM_PHOTO_COUNT_SQL),
+        select_expression_type = diesel::expression::SqlLiteral<diesel::sql_types::BigInt>
+    )]
+    pub photo_count: i64,
+    /// Правило умного альбома, пусто для обычного альбома
+    pub rule: Option<AlbumRule>,
+}
+
+pub const ALBUM_PHOTO_COUNT_SQL: &str =
+    "(SELECT COUNT(*) FROM album_photos WHERE album_photos.album_id = albums.id)";
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct AlbumPhotos {
+    /// Id фотографий
+    pub photo_ids: Vec<i32>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct AlbumCover {
+    /// Id фотографии из альбома, пусто - убрать обложку
+    pub photo_id: Option<i32>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, ToSchema, Clone, Debug, Default)]
@@ -103,6 +321,249 @@ pub struct Album {
 pub struct NewAlbum {
     /// Наименование альбома
     pub title: String,
+    /// Правило, с ним альбом становится умным
+    pub rule: Option<AlbumRule>,
+}
+
+text_enum! {
+    /// Доступ к чужому альбому, каждая роль включает права предыдущих:
+    /// `viewer` смотрит, `contributor` добавляет свои фотографии, `editor`
+    /// убирает фотографии, меняет обложку и правило
+    #[derive(PartialOrd, Ord)]
+    pub enum AlbumRole {
+        Viewer => "viewer",
+        Contributor => "contributor",
+        Editor => "editor",
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::album_shares)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AlbumShare {
+    /// Id альбома
+    pub album_id: i32,
+    /// Id пользователя, получившего доступ
+    pub user_id: i32,
+    /// Уровень доступа
+    pub role: AlbumRole,
+    /// Когда выдан доступ
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct ShareAlbum {
+    /// Уровень доступа
+    pub role: AlbumRole,
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::share_links)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ShareLink {
+    /// Id ссылки
+    pub id: i32,
+    /// Токен для публичного адреса `/api/public/{token}`
+    pub token: String,
+    /// Id пользователя, создавшего ссылку
+    pub user_id: i32,
+    /// Альбом, открытый по ссылке
+    pub album_id: Option<i32>,
+    /// Фотография, открытая по ссылке
+    pub photo_id: Option<i32>,
+    /// Ссылка защищена паролем
+    #[diesel(
+        select_expression = crate::schema::share_links::password.is_not_null(),
+        select_expression_type = diesel::dsl::IsNotNull<crate::schema::share_links::password>
+    )]
+    pub has_password: bool,
+    /// Разрешено скачивать исходные файлы
+    pub allow_download: bool,
+    /// Когда ссылка перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Сколько раз ссылку открыли
+    pub view_count: i32,
+    /// Когда создана ссылка
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct NewShareLink {
+    /// Альбом, нужно указать либо его, либо `photo_id`
+    pub album_id: Option<i32>,
+    /// Фотография
+    pub photo_id: Option<i32>,
+    /// Пароль для открытия ссылки
+    pub password: Option<String>,
+    /// Разрешить скачивание исходных файлов
+    #[serde(default)]
+    pub allow_download: bool,
+    /// Когда ссылка перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct SharedResource {
+    /// Альбом, если ссылка ведёт на альбом
+    pub album: Option<Album>,
+    /// Фотография, если ссылка ведёт на одну фотографию
+    pub photo: Option<ListPhoto>,
+    /// Разрешено скачивать исходные файлы
+    pub allow_download: bool,
+    /// Когда ссылка перестаёт работать
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Параметры списка фотографий альбома по ссылке. Фильтры по тексту и
+/// личностям не принимаются: поиск по тексту обращается к ML, а фильтр по
+/// личностям раскрывал бы, кто есть на фотографиях.
+#[derive(Deserialize, IntoParams, ToSchema, Default)]
+pub struct SharedPhotosQuery {
+    /// Поле сортировки, по умолчанию `taken_at`
+    #[param(inline)]
+    pub sort: Option<PhotoSort>,
+}
+
+/// Файл изображения, отдаваемый через `/api/media`
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaVariant {
+    /// Исходный загруженный файл
+    Original,
+    /// JPEG для показа, повёрнутый по EXIF
+    Image,
+    Thumbnail,
+    Preview,
+    ThumbnailWebp,
+    PreviewWebp,
+}
+
+impl MediaVariant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaVariant::Original => "original",
+            MediaVariant::Image => "image",
+            MediaVariant::Thumbnail => "thumbnail",
+            MediaVariant::Preview => "preview",
+            MediaVariant::ThumbnailWebp => "thumbnail_webp",
+            MediaVariant::PreviewWebp => "preview_webp",
+        }
+    }
+
+    /// Путь к файлу этого вида у фотографии
+    pub fn file_path(&self, photo: Photo) -> Option<String> {
+        match self {
+            MediaVariant::Original => photo.original_path,
+            MediaVariant::Image => photo.path,
+            MediaVariant::Thumbnail => photo.renditions.thumbnail_path,
+            MediaVariant::Preview => photo.renditions.preview_path,
+            MediaVariant::ThumbnailWebp => photo.renditions.thumbnail_webp_path,
+            MediaVariant::PreviewWebp => photo.renditions.preview_webp_path,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams, ToSchema, Default)]
+pub struct SignedMediaQuery {
+    /// Время окончания действия подписи, unix timestamp
+    pub expires: Option<i64>,
+    /// Подпись ссылки, без неё нужна авторизация
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct SignedMediaUrl {
+    /// Адрес файла, доступный без авторизации до `expires_at`
+    pub url: String,
+    /// Когда подпись перестаёт действовать
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Максимальное косинусное расстояние до текста правила по умолчанию
+pub const SMART_ALBUM_MAX_DISTANCE: f64 = 0.3;
+
+/// Правило умного альбома. Альбом содержит фотографии владельца, подходящие
+/// под все заданные условия.
+#[derive(
+    diesel::AsExpression,
+    diesel::FromSqlRow,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+)]
+#[diesel(sql_type = diesel::sql_types::Jsonb)]
+pub struct AlbumRule {
+    /// Описание для поиска по CLIP
+    pub text: Option<String>,
+    /// Максимальное косинусное расстояние до `text`, по умолчанию 0.3
+    pub max_distance: Option<f64>,
+    /// Личности, которые должны быть на фотографии
+    pub person_ids: Option<Vec<i32>>,
+    /// Как сочетать личности: `and` - все на фотографии, `or` - хотя бы одна
+    pub persons_match: Option<PersonsMatch>,
+    /// Снято не раньше этой даты
+    pub taken_from: Option<NaiveDate>,
+    /// Снято не позже этой даты
+    pub taken_to: Option<NaiveDate>,
+    /// Производитель камеры, без учёта регистра
+    pub camera_make: Option<String>,
+    /// Модель камеры, без учёта регистра
+    pub camera_model: Option<String>,
+    /// Широта центра области съёмки
+    pub latitude: Option<f64>,
+    /// Долгота центра области съёмки
+    pub longitude: Option<f64>,
+    /// Радиус области съёмки в километрах
+    pub radius_km: Option<f64>,
+}
+
+impl AlbumRule {
+    /// Фильтры фотографий для `filtered_photos`. Текст в них не передаётся,
+    /// вместо него используется сохранённый эмбеддинг правила.
+    pub fn filters(&self) -> PhotosFilters {
+        PhotosFilters {
+            max_distance: self
+                .text
+                .as_ref()
+                .map(|_| self.max_distance.unwrap_or(SMART_ALBUM_MAX_DISTANCE)),
+            person_ids: self.person_ids.clone(),
+            persons_match: self.persons_match,
+            taken_from: self.taken_from,
+            taken_to: self.taken_to,
+            camera_make: self.camera_make.clone(),
+            camera_model: self.camera_model.clone(),
+            latitude: self.latitude,
+            longitude: self.longitude,
+            radius_km: self.radius_km,
+            ..Default::default()
+        }
+    }
+}
+
+impl diesel::serialize::ToSql<diesel::sql_types::Jsonb, diesel::pg::Pg> for AlbumRule {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> diesel::serialize::Result {
+        use std::io::Write;
+        // Бинарный формат JSONB - версия 1 и текст JSON
+        out.write_all(&[1])?;
+        serde_json::to_writer(&mut *out, self)?;
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+impl diesel::deserialize::FromSql<diesel::sql_types::Jsonb, diesel::pg::Pg> for AlbumRule {
+    fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            [1, json @ ..] => Ok(serde_json::from_slice(json)?),
+            _ => Err("Unsupported JSONB encoding version".into()),
+        }
+    }
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -113,15 +574,237 @@ pub struct SignInData {
     pub password: String,
 }
 
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct RegisterUser {
+    /// Код приглашения, выданный администратором
+    pub invite_code: String,
+    /// Имя пользователя
+    pub username: String,
+    /// Почта пользователя
+    pub email: String,
+    /// Пароль пользователя
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct UpdateProfile {
+    /// Новое имя пользователя
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct ChangePassword {
+    /// Текущий пароль
+    pub current_password: String,
+    /// Новый пароль
+    pub new_password: String,
+}
+
+#[derive(TryFromMultipart, Debug)]
+pub struct AvatarForm {
+    #[form_data(limit = "unlimited")]
+    pub avatar_image: FieldData<Bytes>,
+}
+
+#[derive(ToSchema, Debug)]
+pub struct AvatarFormUtopia {
+    pub avatar_image: Vec<u8>,
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::invites)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Invite {
+    /// Id приглашения
+    pub id: i32,
+    /// Код для регистрации
+    pub code: String,
+    /// Id администратора, выдавшего приглашение
+    pub created_by: i32,
+    /// Почта, для которой выдано приглашение, пусто - для любой
+    pub email: Option<String>,
+    /// Когда приглашение перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Id пользователя, зарегистрированного по приглашению
+    pub used_by: Option<i32>,
+    /// Когда приглашение использовано
+    pub used_at: Option<DateTime<Utc>>,
+    /// Когда выдано приглашение
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct NewInvite {
+    /// Почта, для которой выдаётся приглашение, пусто - для любой
+    pub email: Option<String>,
+    /// Когда приглашение перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct LogoutQuery {
+    /// Завершить все сессии пользователя, а не только текущую
+    #[serde(default)]
+    pub all: bool,
+}
+
+text_enum! {
+    /// Права персонального API ключа, каждая область включает предыдущие:
+    /// `read` только читает, `upload` ещё загружает фотографии, `write`
+    /// изменяет и удаляет данные
+    #[derive(PartialOrd, Ord)]
+    pub enum ApiKeyScope {
+        Read => "read",
+        Upload => "upload",
+        Write => "write",
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::api_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ApiKey {
+    /// Id ключа
+    pub id: i32,
+    /// Id владельца ключа
+    pub user_id: i32,
+    /// Название ключа
+    pub name: String,
+    /// Начало ключа, по которому его можно узнать
+    pub prefix: String,
+    /// Права ключа
+    pub scope: ApiKeyScope,
+    /// Когда ключ перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Когда ключ последний раз использовался
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Когда создан ключ
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct NewApiKey {
+    /// Название ключа, например имя скрипта или приложения
+    pub name: String,
+    /// Права ключа
+    pub scope: ApiKeyScope,
+    /// Когда ключ перестаёт работать, пусто - бессрочно
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct CreatedApiKey {
+    /// Созданный ключ
+    pub api_key: ApiKey,
+    /// Ключ для заголовка `Authorization: Bearer`, показывается только один раз
+    pub key: String,
+}
+
 #[derive(Deserialize, IntoParams, ToSchema)]
 pub struct UsersQuery {
+    /// Часть почты, без учёта регистра
     pub email: Option<String>,
+    /// Часть имени, без учёта регистра
     pub username: Option<String>,
+    /// Поле сортировки, по умолчанию `id`
+    #[param(inline)]
+    pub sort: Option<UserSort>,
+}
+
+sort_field! {
+    pub enum UserSort {
+        #[default]
+        Id => "id",
+        Username => "username",
+        Email => "email",
+    }
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct AlbumsQuery {
+    /// `true` - только альбомы, которыми поделились с пользователем, `false` -
+    /// только собственные
+    pub shared: Option<bool>,
+    /// Поле сортировки, по умолчанию `id`
+    #[param(inline)]
+    pub sort: Option<AlbumSort>,
+}
+
+sort_field! {
+    pub enum AlbumSort {
+        #[default]
+        Id => "id",
+        Title => "title",
+    }
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        }
+    }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "asc" => Some(SortDirection::Asc),
+            "desc" => Some(SortDirection::Desc),
+            _ => None,
+        }
+    }
+}
+
+/// Параметры страницы списка: курсор следующей страницы или смещение
+#[derive(Deserialize, IntoParams, ToSchema, Default)]
+pub struct PageParams {
+    /// `next_cursor` предыдущей страницы, при нём `offset` не учитывается
+    pub cursor: Option<String>,
+    /// Сколько записей пропустить
+    pub offset: Option<i64>,
+    /// Размер страницы, по умолчанию 50, не больше 500
+    pub limit: Option<i64>,
+    /// Направление сортировки, по умолчанию своё для каждого поля
+    #[param(inline)]
+    pub direction: Option<SortDirection>,
+}
+
+/// Страница списка
+#[derive(Serialize, ToSchema, Clone, Debug)]
+#[aliases(
+    UserPage = Page<User>,
+    AlbumPage = Page<Album>,
+    PhotoPage = Page<ListPhoto>,
+    PersonPage = Page<ListPerson>,
+    FacePage = Page<ListFace>
+)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Количество записей во всём списке
+    pub total: i64,
+    /// Курсор следующей страницы, пусто на последней
+    pub next_cursor: Option<String>,
 }
 
 #[derive(TryFromMultipart, Debug)]
 pub struct PhotoForm {
     pub title: Option<String>,
+    /// Альбом, в который добавляется фотография: свой или общий с ролью `contributor`
     pub album_id: Option<i32>,
     #[form_data(limit = "unlimited")]
     pub photo_image: FieldData<Bytes>,
@@ -134,18 +817,180 @@ pub struct PhotoFormUtopia {
     pub album_id: Option<i32>,
 }
 
+#[derive(TryFromMultipart, Debug)]
+pub struct SimilarPhotoForm {
+    #[form_data(limit = "unlimited")]
+    pub photo_image: FieldData<Bytes>,
+}
+
+#[derive(ToSchema, Debug)]
+pub struct SimilarPhotoFormUtopia {
+    pub photo_image: Vec<u8>,
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct SimilarPhotosFilters {
+    /// Максимальное косинусное расстояние до исходного изображения
+    pub max_distance: Option<f64>,
+    pub qty: Option<i32>,
+}
+
+/// Похожие друг на друга фотографии одного пользователя
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct DuplicateGroup {
+    pub photos: Vec<ListPhoto>,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct ResolveDuplicates {
+    /// Id фотографии, которая остаётся
+    pub keep: i32,
+    /// Id удаляемых фотографий
+    pub delete: Vec<i32>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct SimilarPhoto {
+    pub photo: ListPhoto,
+    /// Косинусное расстояние до исходного изображения
+    pub distance: f64,
+}
+
 #[derive(Deserialize, IntoParams, ToSchema)]
 pub struct SearchQuery {
     pub text: Option<String>,
 }
 
-#[derive(Deserialize, IntoParams, ToSchema)]
+#[derive(Deserialize, IntoParams, ToSchema, Default)]
 pub struct PhotosFilters {
+    /// Поиск по описанию, при нём фотографии упорядочены по релевантности
     pub text: Option<String>,
-    pub qty: Option<i32>,
+    /// Максимальное косинусное расстояние до `text`
+    pub max_distance: Option<f64>,
+    /// Фотографии альбома
+    pub album_id: Option<i32>,
+    /// Поле сортировки без `text`, по умолчанию `taken_at`
+    #[param(inline)]
+    pub sort: Option<PhotoSort>,
+    /// Id личности, которая должна быть на фотографии
+    pub person_id: Option<i32>,
+    /// Несколько личностей, повторяющийся параметр `person_ids=1&person_ids=2`
+    pub person_ids: Option<Vec<i32>>,
+    /// Как сочетать личности: `and` - все на фотографии, `or` - хотя бы одна
+    #[param(inline)]
+    pub persons_match: Option<PersonsMatch>,
+    /// Снято не раньше этой даты
+    pub taken_from: Option<NaiveDate>,
+    /// Снято не позже этой даты
+    pub taken_to: Option<NaiveDate>,
+    /// Производитель камеры, без учёта регистра
+    pub camera_make: Option<String>,
+    /// Модель камеры, без учёта регистра
+    pub camera_model: Option<String>,
+    /// Модель объектива, без учёта регистра
+    pub lens_model: Option<String>,
+    /// Минимальное ISO
+    pub iso_min: Option<i32>,
+    /// Максимальное ISO
+    pub iso_max: Option<i32>,
+    /// `true` - только с координатами, `false` - только без них
+    pub has_location: Option<bool>,
+    /// Широта центра области съёмки, вместе с `longitude` и `radius_km`
+    pub latitude: Option<f64>,
+    /// Долгота центра области съёмки
+    pub longitude: Option<f64>,
+    /// Радиус области съёмки в километрах
+    pub radius_km: Option<f64>,
 }
 
-#[derive(Queryable, Selectable, ToSchema, Clone, Debug)]
+impl PhotosFilters {
+    /// Поле сортировки страниц. При поиске по тексту фотографии упорядочены
+    /// по релевантности, и страницы листаются смещением.
+    pub fn page_sort(&self) -> Option<PhotoSort> {
+        match self.text {
+            Some(_) => None,
+            None => Some(self.sort.unwrap_or_default()),
+        }
+    }
+
+    pub fn all_person_ids(&self) -> Vec<i32> {
+        let mut ids: Vec<i32> = self
+            .person_id
+            .into_iter()
+            .chain(self.person_ids.iter().flatten().copied())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+sort_field! {
+    pub enum PhotoSort {
+        #[default]
+        TakenAt => "taken_at",
+        Id => "id",
+        Title => "title",
+    }
+}
+
+#[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineGranularity {
+    Year,
+    #[default]
+    Month,
+    Day,
+}
+
+impl TimelineGranularity {
+    /// Единица для `date_trunc` в Postgres
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineGranularity::Year => "year",
+            TimelineGranularity::Month => "month",
+            TimelineGranularity::Day => "day",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct TimelineQuery {
+    /// Размер периода: `year`, `month` (по умолчанию) или `day`
+    #[param(inline)]
+    pub granularity: Option<TimelineGranularity>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct TimelineBucket {
+    /// Первый день периода, пусто для фотографий без даты съёмки
+    pub date: Option<NaiveDate>,
+    /// Количество фотографий в периоде
+    pub count: i64,
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct TimelinePhotosQuery {
+    /// Размер периода: `year`, `month` (по умолчанию) или `day`
+    #[param(inline)]
+    pub granularity: Option<TimelineGranularity>,
+    /// Любой день периода, без него возвращаются фотографии без даты съёмки
+    pub date: Option<NaiveDate>,
+    /// Сколько фотографий пропустить
+    pub offset: Option<i64>,
+    /// Сколько фотографий вернуть
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PersonsMatch {
+    #[default]
+    And,
+    Or,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, ToSchema, Clone, Debug)]
 #[diesel(table_name = crate::schema::persons)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Person {
@@ -155,6 +1000,8 @@ pub struct Person {
     pub title: String,
     /// Путь к аватару личности
     pub avatar: String,
+    /// Id пользователя, на фотографиях которого найдена личность
+    pub user_id: i32,
 }
 
 #[derive(Insertable, ToSchema, Clone, Debug, Default)]
@@ -164,6 +1011,100 @@ pub struct NewPerson {
     pub title: String,
     /// Путь к аватару личности
     pub avatar: String,
+    /// Id пользователя, на фотографиях которого найдена личность
+    pub user_id: i32,
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct PersonsQuery {
+    /// Поле сортировки, по умолчанию `face_count`
+    #[param(inline)]
+    pub sort: Option<PersonSort>,
+}
+
+sort_field! {
+    pub enum PersonSort {
+        #[default]
+        FaceCount => "face_count",
+        Id => "id",
+        Title => "title",
+    }
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct ListPerson {
+    /// Id личности
+    pub id: i32,
+    /// Наименование личности
+    pub title: String,
+    /// Путь к аватару личности
+    pub avatar: String,
+    /// Количество лиц личности
+    pub face_count: i64,
+}
+
+impl From<(Person, i64)> for ListPerson {
+    fn from((person, face_count): (Person, i64)) -> Self {
+        ListPerson {
+            id: person.id,
+            title: person.title,
+            avatar: person.avatar,
+            face_count,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct PersonDetail {
+    /// Id личности
+    pub id: i32,
+    /// Наименование личности
+    pub title: String,
+    /// Путь к аватару личности
+    pub avatar: String,
+    /// Лица личности
+    pub faces: Vec<ListFace>,
+    /// Фотографии, на которых есть личность
+    pub photos: Vec<ListPhoto>,
+}
+
+#[derive(AsChangeset, Deserialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::persons)]
+pub struct UpdatePerson {
+    /// Наименование личности
+    pub title: String,
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct MergePersons {
+    /// Id личности, лица которой переносятся и которая затем удаляется
+    pub source_id: i32,
+}
+
+#[derive(TryFromMultipart, Debug)]
+pub struct FaceSearchForm {
+    #[form_data(limit = "unlimited")]
+    pub face_image: FieldData<Bytes>,
+}
+
+#[derive(ToSchema, Debug)]
+pub struct FaceSearchFormUtopia {
+    pub face_image: Vec<u8>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct MatchedPerson {
+    pub person: Person,
+    /// Косинусное расстояние до ближайшего лица личности
+    pub distance: f64,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct FaceSearchResult {
+    /// Личности в порядке возрастания расстояния
+    pub persons: Vec<MatchedPerson>,
+    /// Фотографии с похожими лицами в порядке возрастания расстояния
+    pub photos: Vec<SimilarPhoto>,
 }
 
 #[derive(Queryable, Selectable, ToSchema, Clone, Debug)]
@@ -176,6 +1117,8 @@ pub struct Face {
     pub embedding: Option<Vector>,
     pub bbox: Option<Vec<Option<i32>>>,
     pub path: Option<String>,
+    pub is_ignored: bool,
+    pub is_manual: bool,
 }
 
 #[derive(Insertable, ToSchema, Clone, Debug, Default)]
@@ -183,3 +1126,120 @@ pub struct Face {
 pub struct NewFace {
     pub photo_id: i32,
 }
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::faces)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ListFace {
+    /// Id лица
+    pub id: i32,
+    /// Id личности
+    pub person_id: Option<i32>,
+    /// Id изображения, на котором найдено лицо
+    pub photo_id: i32,
+    /// Рамка лица на изображении
+    pub bbox: Option<Vec<Option<i32>>>,
+    /// Путь к вырезанному изображению лица
+    pub path: Option<String>,
+    /// Лицо помечено как "не лицо" и не участвует в сопоставлении
+    pub is_ignored: bool,
+    /// Личность назначена пользователем вручную
+    pub is_manual: bool,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::schema::face_rejections)]
+pub struct FaceRejection {
+    /// Id лица
+    pub face_id: i32,
+    /// Id личности, из которой лицо было убрано
+    pub person_id: i32,
+}
+
+#[derive(Deserialize, IntoParams, ToSchema)]
+pub struct FacesQuery {
+    /// Лица личности
+    pub person_id: Option<i32>,
+    /// Лица на изображении
+    pub photo_id: Option<i32>,
+    /// `true` - только помеченные как "не лицо", `false` - только остальные
+    pub is_ignored: Option<bool>,
+    /// Поле сортировки, по умолчанию `id`
+    #[param(inline)]
+    pub sort: Option<FaceSort>,
+}
+
+sort_field! {
+    pub enum FaceSort {
+        #[default]
+        Id => "id",
+        PhotoId => "photo_id",
+    }
+}
+
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+pub struct MoveFace {
+    /// Id личности, в которую переносится лицо
+    pub person_id: i32,
+}
+
+text_enum! {
+    /// Тип фоновой задачи
+    pub enum JobKind {
+        ProcessPhoto => "process_photo",
+        GenerateRenditions => "generate_renditions",
+    }
+}
+
+text_enum! {
+    /// Состояние фоновой задачи
+    pub enum JobStatus {
+        Pending => "pending",
+        Running => "running",
+        Done => "done",
+        Failed => "failed",
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = crate::schema::jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Job {
+    /// Id задачи
+    pub id: i32,
+    /// Тип задачи
+    pub kind: JobKind,
+    /// Id изображения, которое обрабатывает задача
+    pub photo_id: Option<i32>,
+    /// Состояние задачи
+    pub status: JobStatus,
+    /// Количество сделанных попыток
+    pub attempts: i32,
+    /// Максимальное количество попыток
+    pub max_attempts: i32,
+    /// Время следующего запуска
+    pub run_at: DateTime<Utc>,
+    /// Текст последней ошибки
+    pub last_error: Option<String>,
+    /// Время создания задачи
+    pub created_at: DateTime<Utc>,
+    /// Время последнего изменения задачи
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::schema::jobs)]
+pub struct NewJob {
+    /// Тип задачи
+    pub kind: JobKind,
+    /// Id изображения, которое обрабатывает задача
+    pub photo_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct UploadedPhoto {
+    /// Id изображения
+    pub photo_id: i32,
+    /// Id задачи обработки изображения
+    pub job_id: i32,
+}
@@ -1,20 +1,43 @@
 use axum::{
-    extract::Path,
-    routing::{get, post},
+    extract::{Extension, Path, Query, State},
+    routing::{get, post, put},
     Json, Router,
 };
 
 use crate::{
+    middleware::errors::Error,
     models::*,
     services::albums::{
-        create_album, delete_album_by_id, get_album_by_id, get_albums_with_filters,
+        add_photos_to_album, create_album, delete_album_by_id, get_album_by_id, get_album_shares,
+        get_albums_with_filters, refresh_album, remove_photos_from_album, set_album_cover,
+        set_album_rule, share_album, unshare_album,
     },
+    services::pagination::PageQuery,
+    services::photos::get_photos_by_filters,
+    state::AppState,
 };
 
-pub async fn router() -> Router {
+pub async fn router() -> Router<AppState> {
     Router::new()
-        .route("/", post(post_album))
+        .route("/", get(get_albums).post(post_album))
         .route("/:album_id", get(get_album).delete(delete_album))
+        .route(
+            "/:album_id/photos",
+            get(get_album_photos)
+                .post(post_album_photos)
+                .delete(delete_album_photos),
+        )
+        .route("/:album_id/cover", put(put_album_cover))
+        .route(
+            "/:album_id/rule",
+            put(put_album_rule).delete(delete_album_rule),
+        )
+        .route("/:album_id/refresh", post(post_album_refresh))
+        .route("/:album_id/shares", get(get_shares))
+        .route(
+            "/:album_id/shares/:user_id",
+            put(put_share).delete(delete_share),
+        )
 }
 
 #[utoipa::path(
@@ -22,11 +45,18 @@ pub async fn router() -> Router {
     path = "/api/album",
     request_body = NewAlbum,
     responses(
-        (status = 201, description = "Create album", body = Album)
+        (status = 201, description = "Create album, with a rule it is a smart album", body = Album),
+        (status = 400, description = "Invalid album rule")
     )
 )]
-pub async fn post_album(Json(new_album): Json<NewAlbum>) -> Json<Album> {
-    Json(create_album(new_album).await)
+pub async fn post_album(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(new_album): Json<NewAlbum>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        create_album(&state.pool, state.ml.as_ref(), new_album, user.id).await?,
+    ))
 }
 
 #[utoipa::path(
@@ -37,8 +67,13 @@ pub async fn post_album(Json(new_album): Json<NewAlbum>) -> Json<Album> {
         (status = 201, description = "Create album")
     )
 )]
-pub async fn delete_album(Path(album_id): Path<i32>) {
-    delete_album_by_id(album_id).await;
+pub async fn delete_album(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+) -> Result<(), Error> {
+    delete_album_by_id(&state.pool, Owner::of(&user, &state.config.auth), album_id).await?;
+    Ok(())
 }
 
 #[utoipa::path(
@@ -49,17 +84,324 @@ pub async fn delete_album(Path(album_id): Path<i32>) {
         (status = 200, description = "Detail info about album", body = Album)
     )
 )]
-pub async fn get_album(Path(album_id): Path<i32>) -> Json<Album> {
-    Json(get_album_by_id(album_id).await)
+pub async fn get_album(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        get_album_by_id(&state.pool, Owner::of(&user, &state.config.auth), album_id).await?,
+    ))
 }
 
 #[utoipa::path(
     get,
     path = "/api/album",
+    params(AlbumsQuery, PageParams),
     responses(
-        (status = 200, description = "Detail info about album", body = Vec<Album>)
+        (status = 200, description = "Page of own albums and albums shared with user", body = AlbumPage),
+        (status = 400, description = "Invalid cursor")
     )
 )]
-pub async fn get_albums() -> Json<Vec<Album>> {
-    Json(get_albums_with_filters().await)
+pub async fn get_albums(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<AlbumsQuery>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<Album>>, Error> {
+    let page = PageQuery::new(&page_params, Some(params.sort.unwrap_or_default()))?;
+    Ok(Json(
+        get_albums_with_filters(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            user.id,
+            params.shared,
+            page,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/album/{album_id}/photos",
+    tag = "albums",
+    params(
+        ("album_id" = i32, Path, description = "Id of album"),
+        PhotosFilters,
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Page of album photos", body = PhotoPage),
+        (status = 400, description = "Invalid cursor"),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn get_album_photos(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+    Query(mut filters): Query<PhotosFilters>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListPhoto>>, Error> {
+    get_album_by_id(&state.pool, Owner::of(&user, &state.config.auth), album_id).await?;
+
+    let page = PageQuery::new(&page_params, filters.page_sort())?;
+    filters.album_id = Some(album_id);
+
+    // В общем альбоме есть фотографии владельца и участников, доступ к ним
+    // дан доступом к альбому
+    Ok(Json(
+        get_photos_by_filters(&state.pool, state.ml.as_ref(), Owner::Any, filters, page).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/album/{album_id}/photos",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    request_body = AlbumPhotos,
+    responses(
+        (status = 200, description = "Add photos to album", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 409, description = "Photos of a smart album are chosen by its rule"),
+        (status = 404, description = "Album or photo not found")
+    )
+)]
+pub async fn post_album_photos(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+    Json(album_photos): Json<AlbumPhotos>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        add_photos_to_album(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            album_photos.photo_ids,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/album/{album_id}/photos",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    request_body = AlbumPhotos,
+    responses(
+        (status = 200, description = "Remove photos from album", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 409, description = "Photos of a smart album are chosen by its rule"),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn delete_album_photos(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+    Json(album_photos): Json<AlbumPhotos>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        remove_photos_from_album(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            album_photos.photo_ids,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/album/{album_id}/cover",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    request_body = AlbumCover,
+    responses(
+        (status = 200, description = "Set album cover photo", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 404, description = "Album not found or photo is not in album")
+    )
+)]
+pub async fn put_album_cover(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+    Json(cover): Json<AlbumCover>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        set_album_cover(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            cover.photo_id,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/album/{album_id}/rule",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    request_body = AlbumRule,
+    responses(
+        (status = 200, description = "Make album smart and fill it by the rule", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 400, description = "Invalid album rule"),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn put_album_rule(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+    Json(rule): Json<AlbumRule>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        set_album_rule(
+            &state.pool,
+            state.ml.as_ref(),
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            Some(rule),
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/album/{album_id}/rule",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    responses(
+        (status = 200, description = "Make album regular keeping its photos", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn delete_album_rule(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        set_album_rule(
+            &state.pool,
+            state.ml.as_ref(),
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            None,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/album/{album_id}/refresh",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    responses(
+        (status = 200, description = "Re-evaluate smart album rule", body = Album),
+        (status = 403, description = "Not enough permissions for the album"),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn post_album_refresh(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+) -> Result<Json<Album>, Error> {
+    Ok(Json(
+        refresh_album(&state.pool, Owner::of(&user, &state.config.auth), album_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/album/{album_id}/shares",
+    tag = "albums",
+    params(("album_id" = i32, Path, description = "Id of album")),
+    responses(
+        (status = 200, description = "Users the album is shared with", body = [AlbumShare]),
+        (status = 404, description = "Album not found")
+    )
+)]
+pub async fn get_shares(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(album_id): Path<i32>,
+) -> Result<Json<Vec<AlbumShare>>, Error> {
+    Ok(Json(
+        get_album_shares(&state.pool, Owner::of(&user, &state.config.auth), album_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/album/{album_id}/shares/{user_id}",
+    tag = "albums",
+    params(
+        ("album_id" = i32, Path, description = "Id of album"),
+        ("user_id" = i32, Path, description = "Id of user to share with")
+    ),
+    request_body = ShareAlbum,
+    responses(
+        (status = 200, description = "Share album or change user role", body = AlbumShare),
+        (status = 400, description = "Album cannot be shared with its owner"),
+        (status = 404, description = "Album or user not found")
+    )
+)]
+pub async fn put_share(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path((album_id, user_id)): Path<(i32, i32)>,
+    Json(share): Json<ShareAlbum>,
+) -> Result<Json<AlbumShare>, Error> {
+    Ok(Json(
+        share_album(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            album_id,
+            user_id,
+            share.role,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/album/{album_id}/shares/{user_id}",
+    tag = "albums",
+    params(
+        ("album_id" = i32, Path, description = "Id of album"),
+        ("user_id" = i32, Path, description = "Id of user, can be the current user to leave the album")
+    ),
+    responses(
+        (status = 200, description = "Revoke access to album"),
+        (status = 404, description = "Album or share not found")
+    )
+)]
+pub async fn delete_share(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path((album_id, user_id)): Path<(i32, i32)>,
+) -> Result<(), Error> {
+    unshare_album(
+        &state.pool,
+        Owner::of(&user, &state.config.auth),
+        album_id,
+        user_id,
+    )
+    .await?;
+    Ok(())
 }
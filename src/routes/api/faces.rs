@@ -0,0 +1,175 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::{
+    middleware::errors::Error,
+    models::*,
+    services::faces::{
+        detach_face, get_face_by_id, get_faces, move_face_to_person, set_face_ignored,
+    },
+    services::pagination::PageQuery,
+    state::AppState,
+};
+
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_faces_list))
+        .route("/:face_id", get(get_face))
+        .route("/:face_id/person", post(post_face_person))
+        .route("/:face_id/detach", post(post_detach_face))
+        .route(
+            "/:face_id/ignore",
+            post(post_ignore_face).delete(delete_ignore_face),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/face",
+    tag = "faces",
+    params(FacesQuery, PageParams),
+    responses(
+        (status = 200, description = "Page of faces", body = FacePage),
+        (status = 400, description = "Invalid cursor")
+    )
+)]
+pub async fn get_faces_list(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<FacesQuery>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListFace>>, Error> {
+    let page = PageQuery::new(&page_params, Some(params.sort.unwrap_or_default()))?;
+    Ok(Json(
+        get_faces(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            params,
+            page,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/face/{face_id}",
+    tag = "faces",
+    params(("face_id" = i32, Path, description = "Id of face")),
+    responses(
+        (status = 200, description = "Detail info about face", body = ListFace),
+        (status = 404, description = "Face not found")
+    )
+)]
+pub async fn get_face(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(face_id): Path<i32>,
+) -> Result<Json<ListFace>, Error> {
+    Ok(Json(
+        get_face_by_id(&state.pool, Owner::of(&user, &state.config.auth), face_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/face/{face_id}/person",
+    tag = "faces",
+    params(("face_id" = i32, Path, description = "Id of face")),
+    request_body = MoveFace,
+    responses(
+        (status = 200, description = "Move face to another person", body = ListFace),
+        (status = 404, description = "Face or person not found")
+    )
+)]
+pub async fn post_face_person(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(face_id): Path<i32>,
+    Json(move_face): Json<MoveFace>,
+) -> Result<Json<ListFace>, Error> {
+    Ok(Json(
+        move_face_to_person(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            face_id,
+            move_face.person_id,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/face/{face_id}/detach",
+    tag = "faces",
+    params(("face_id" = i32, Path, description = "Id of face")),
+    responses(
+        (status = 200, description = "Move face to a new person", body = Person),
+        (status = 404, description = "Face not found")
+    )
+)]
+pub async fn post_detach_face(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(face_id): Path<i32>,
+) -> Result<Json<Person>, Error> {
+    Ok(Json(
+        detach_face(&state.pool, Owner::of(&user, &state.config.auth), face_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/face/{face_id}/ignore",
+    tag = "faces",
+    params(("face_id" = i32, Path, description = "Id of face")),
+    responses(
+        (status = 200, description = "Mark face as not a face", body = ListFace),
+        (status = 404, description = "Face not found")
+    )
+)]
+pub async fn post_ignore_face(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(face_id): Path<i32>,
+) -> Result<Json<ListFace>, Error> {
+    Ok(Json(
+        set_face_ignored(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            face_id,
+            true,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/face/{face_id}/ignore",
+    tag = "faces",
+    params(("face_id" = i32, Path, description = "Id of face")),
+    responses(
+        (status = 200, description = "Remove not a face mark", body = ListFace),
+        (status = 404, description = "Face not found")
+    )
+)]
+pub async fn delete_ignore_face(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(face_id): Path<i32>,
+) -> Result<Json<ListFace>, Error> {
+    Ok(Json(
+        set_face_ignored(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            face_id,
+            false,
+        )
+        .await?,
+    ))
+}
@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Extension, Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::{
+    middleware::errors::Error,
+    models::*,
+    services::jobs::{get_job_by_id, retry_job},
+    state::AppState,
+};
+
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/:job_id", get(get_job))
+        .route("/:job_id/retry", post(post_retry_job))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/job/{job_id}",
+    tag = "jobs",
+    params(("job_id" = i32, Path, description = "Id of job")),
+    responses(
+        (status = 200, description = "Job status", body = Job),
+        (status = 404, description = "Job not found")
+    )
+)]
+pub async fn get_job(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(job_id): Path<i32>,
+) -> Result<Json<Job>, Error> {
+    Ok(Json(
+        get_job_by_id(&state.pool, Owner::of(&user, &state.config.auth), job_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/job/{job_id}/retry",
+    tag = "jobs",
+    params(("job_id" = i32, Path, description = "Id of job")),
+    responses(
+        (status = 200, description = "Failed job is queued again", body = Job),
+        (status = 404, description = "Failed job not found")
+    )
+)]
+pub async fn post_retry_job(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(job_id): Path<i32>,
+) -> Result<Json<Job>, Error> {
+    Ok(Json(
+        retry_job(&state.pool, Owner::of(&user, &state.config.auth), job_id).await?,
+    ))
+}
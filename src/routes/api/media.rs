@@ -0,0 +1,231 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, Response},
+    routing::get,
+    Json, Router,
+};
+use tower_cookies::Cookies;
+
+use crate::{
+    middleware::{authorize::current_user, errors::Error},
+    models::*,
+    services::media::{
+        avatar_media_path, face_media_path, get_avatar_file, get_face_file, get_photo_file,
+        photo_media_path, serve_media_file, signed_media_url, verify_media_signature,
+    },
+    state::AppState,
+};
+
+/// Файлы доступны по авторизации либо по подписанной ссылке
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/photo/:photo_id/:variant", get(get_photo_media))
+        .route("/photo/:photo_id/:variant/signed", get(get_photo_media_url))
+        .route("/face/:face_id", get(get_face_media))
+        .route("/face/:face_id/signed", get(get_face_media_url))
+        .route("/avatar/:user_id", get(get_avatar_media))
+        .route("/avatar/:user_id/signed", get(get_avatar_media_url))
+}
+
+/// Чьи файлы доступны запросу: по верной подписи - любые, иначе - файлы,
+/// видимые авторизованному пользователю
+async fn media_owner(
+    state: &AppState,
+    cookies: &Cookies,
+    headers: &HeaderMap,
+    path: &str,
+    signed: &SignedMediaQuery,
+) -> Result<Owner, Error> {
+    if signed.signature.is_some() {
+        verify_media_signature(path, signed, &state.config)?;
+        return Ok(Owner::Any);
+    }
+    Ok(Owner::of(
+        &current_user(state, cookies, headers).await?,
+        &state.config.auth,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/photo/{photo_id}/{variant}",
+    tag = "media",
+    params(
+        ("photo_id" = i32, Path, description = "Photo id"),
+        ("variant" = MediaVariant, Path, description = "Photo file variant"),
+        SignedMediaQuery
+    ),
+    responses(
+        (status = 200, description = "Photo file, supports Range and conditional requests"),
+        (status = 206, description = "Part of photo file"),
+        (status = 304, description = "Photo file is not modified"),
+        (status = 401, description = "No authorization and no signature"),
+        (status = 403, description = "Invalid or expired media signature"),
+        (status = 404, description = "Photo or file not found")
+    )
+)]
+pub async fn get_photo_media(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path((photo_id, variant)): Path<(i32, MediaVariant)>,
+    Query(signed): Query<SignedMediaQuery>,
+    req: Request,
+) -> Result<Response<Body>, Error> {
+    let path = photo_media_path(photo_id, variant);
+    let owner = media_owner(&state, &cookies, &headers, &path, &signed).await?;
+
+    let file_path = get_photo_file(&state.pool, owner, photo_id, variant).await?;
+    Ok(serve_media_file(state.storage.as_ref(), &file_path, req, &state.config).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/photo/{photo_id}/{variant}/signed",
+    tag = "media",
+    params(
+        ("photo_id" = i32, Path, description = "Photo id"),
+        ("variant" = MediaVariant, Path, description = "Photo file variant")
+    ),
+    responses(
+        (status = 200, description = "Short-lived URL of photo file for <img> tags", body = SignedMediaUrl),
+        (status = 404, description = "Photo or file not found")
+    )
+)]
+pub async fn get_photo_media_url(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path((photo_id, variant)): Path<(i32, MediaVariant)>,
+) -> Result<Json<SignedMediaUrl>, Error> {
+    let user = current_user(&state, &cookies, &headers).await?;
+    let file_path = get_photo_file(
+        &state.pool,
+        Owner::of(&user, &state.config.auth),
+        photo_id,
+        variant,
+    )
+    .await?;
+
+    Ok(Json(signed_media_url(
+        state.storage.as_ref(),
+        &file_path,
+        &photo_media_path(photo_id, variant),
+        &state.config,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/face/{face_id}",
+    tag = "media",
+    params(("face_id" = i32, Path, description = "Face id"), SignedMediaQuery),
+    responses(
+        (status = 200, description = "Face crop, supports Range and conditional requests"),
+        (status = 206, description = "Part of face crop"),
+        (status = 304, description = "Face crop is not modified"),
+        (status = 401, description = "No authorization and no signature"),
+        (status = 403, description = "Invalid or expired media signature"),
+        (status = 404, description = "Face or file not found")
+    )
+)]
+pub async fn get_face_media(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(face_id): Path<i32>,
+    Query(signed): Query<SignedMediaQuery>,
+    req: Request,
+) -> Result<Response<Body>, Error> {
+    let path = face_media_path(face_id);
+    let owner = media_owner(&state, &cookies, &headers, &path, &signed).await?;
+
+    let file_path = get_face_file(&state.pool, owner, face_id).await?;
+    Ok(serve_media_file(state.storage.as_ref(), &file_path, req, &state.config).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/face/{face_id}/signed",
+    tag = "media",
+    params(("face_id" = i32, Path, description = "Face id")),
+    responses(
+        (status = 200, description = "Short-lived URL of face crop for <img> tags", body = SignedMediaUrl),
+        (status = 404, description = "Face or file not found")
+    )
+)]
+pub async fn get_face_media_url(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(face_id): Path<i32>,
+) -> Result<Json<SignedMediaUrl>, Error> {
+    let user = current_user(&state, &cookies, &headers).await?;
+    let file_path =
+        get_face_file(&state.pool, Owner::of(&user, &state.config.auth), face_id).await?;
+
+    Ok(Json(signed_media_url(
+        state.storage.as_ref(),
+        &file_path,
+        &face_media_path(face_id),
+        &state.config,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/avatar/{user_id}",
+    tag = "media",
+    params(("user_id" = i32, Path, description = "User id"), SignedMediaQuery),
+    responses(
+        (status = 200, description = "User avatar, supports Range and conditional requests"),
+        (status = 206, description = "Part of user avatar"),
+        (status = 304, description = "User avatar is not modified"),
+        (status = 401, description = "No authorization and no signature"),
+        (status = 403, description = "Invalid or expired media signature"),
+        (status = 404, description = "User or avatar not found")
+    )
+)]
+pub async fn get_avatar_media(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(user_id): Path<i32>,
+    Query(signed): Query<SignedMediaQuery>,
+    req: Request,
+) -> Result<Response<Body>, Error> {
+    // Аватары видны любому пользователю, владелец файла не проверяется
+    let path = avatar_media_path(user_id);
+    media_owner(&state, &cookies, &headers, &path, &signed).await?;
+
+    let file_path = get_avatar_file(&state.pool, user_id).await?;
+    Ok(serve_media_file(state.storage.as_ref(), &file_path, req, &state.config).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/media/avatar/{user_id}/signed",
+    tag = "media",
+    params(("user_id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Short-lived URL of user avatar for <img> tags", body = SignedMediaUrl),
+        (status = 404, description = "User or avatar not found")
+    )
+)]
+pub async fn get_avatar_media_url(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(user_id): Path<i32>,
+) -> Result<Json<SignedMediaUrl>, Error> {
+    current_user(&state, &cookies, &headers).await?;
+    let file_path = get_avatar_file(&state.pool, user_id).await?;
+
+    Ok(Json(signed_media_url(
+        state.storage.as_ref(),
+        &file_path,
+        &avatar_media_path(user_id),
+        &state.config,
+    )))
+}
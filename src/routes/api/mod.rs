@@ -1,31 +1,98 @@
 use axum::{middleware, routing::post, Router};
 
 use crate::middleware::authorize;
+use crate::state::AppState;
 
 pub mod albums;
+pub mod faces;
+pub mod jobs;
+pub mod media;
+pub mod persons;
 pub mod photos;
+pub mod public;
 pub mod security;
+pub mod settings;
+pub mod share_links;
+pub mod timeline;
 pub mod users;
 
-pub async fn api_router() -> Router {
+pub async fn api_router(state: AppState) -> Router<AppState> {
     Router::new()
         .nest(
             "/user",
-            users::router()
-                .await
-                .layer(middleware::from_fn(authorize::authorize)),
+            users::router().await.layer(middleware::from_fn_with_state(
+                state.clone(),
+                authorize::authorize,
+            )),
         )
         .nest(
             "/album",
-            albums::router()
-                .await
-                .layer(middleware::from_fn(authorize::authorize)),
+            albums::router().await.layer(middleware::from_fn_with_state(
+                state.clone(),
+                authorize::authorize,
+            )),
         )
         .nest(
             "/photo",
-            photos::router()
+            photos::router().await.layer(middleware::from_fn_with_state(
+                state.clone(),
+                authorize::authorize,
+            )),
+        )
+        .nest(
+            "/person",
+            persons::router()
+                .await
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    authorize::authorize,
+                )),
+        )
+        .nest(
+            "/face",
+            faces::router().await.layer(middleware::from_fn_with_state(
+                state.clone(),
+                authorize::authorize,
+            )),
+        )
+        .nest(
+            "/job",
+            jobs::router().await.layer(middleware::from_fn_with_state(
+                state.clone(),
+                authorize::authorize,
+            )),
+        )
+        .nest(
+            "/timeline",
+            timeline::router()
+                .await
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    authorize::authorize,
+                )),
+        )
+        .nest(
+            "/share",
+            share_links::router()
+                .await
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    authorize::authorize,
+                )),
+        )
+        .nest(
+            "/settings",
+            settings::router()
                 .await
-                .layer(middleware::from_fn(authorize::authorize)),
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    authorize::authorize,
+                )),
         )
+        .nest("/public", public::router().await)
+        .nest("/media", media::router().await)
         .route("/signin", post(security::sign_in))
+        .route("/auth/refresh", post(security::refresh))
+        .route("/auth/logout", post(security::logout))
+        .route("/register", post(security::register))
 }
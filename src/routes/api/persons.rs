@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_typed_multipart::TypedMultipart;
+use pgvector::Vector;
+
+use crate::{
+    middleware::errors::Error,
+    models::*,
+    services::pagination::PageQuery,
+    services::persons::{
+        delete_person_by_id, get_person_by_id, get_persons_with_faces, merge_persons,
+        search_by_face_embedding, update_person_by_id,
+    },
+    state::AppState,
+};
+
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_persons))
+        .route(
+            "/:person_id",
+            get(get_person).patch(patch_person).delete(delete_person),
+        )
+        .route("/:person_id/merge", post(post_merge_person))
+        .route("/search", post(search_by_face))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/person",
+    tag = "persons",
+    params(PersonsQuery, PageParams),
+    responses(
+        (status = 200, description = "Page of persons with face count", body = PersonPage),
+        (status = 400, description = "Invalid cursor")
+    )
+)]
+pub async fn get_persons(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<PersonsQuery>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListPerson>>, Error> {
+    let page = PageQuery::new(&page_params, Some(params.sort.unwrap_or_default()))?;
+    Ok(Json(
+        get_persons_with_faces(&state.pool, Owner::of(&user, &state.config.auth), page).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/person/{person_id}",
+    tag = "persons",
+    params(("person_id" = i32, Path, description = "Id of person")),
+    responses(
+        (status = 200, description = "Person with faces and photos", body = PersonDetail),
+        (status = 404, description = "Person not found")
+    )
+)]
+pub async fn get_person(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(person_id): Path<i32>,
+) -> Result<Json<PersonDetail>, Error> {
+    Ok(Json(
+        get_person_by_id(&state.pool, Owner::of(&user, &state.config.auth), person_id).await?,
+    ))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/person/{person_id}",
+    tag = "persons",
+    params(("person_id" = i32, Path, description = "Id of person")),
+    request_body = UpdatePerson,
+    responses(
+        (status = 200, description = "Rename person", body = Person),
+        (status = 404, description = "Person not found")
+    )
+)]
+pub async fn patch_person(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(person_id): Path<i32>,
+    Json(changes): Json<UpdatePerson>,
+) -> Result<Json<Person>, Error> {
+    let title_len = changes.title.trim().chars().count();
+    if title_len == 0 || title_len > 50 {
+        return Err(Error::new(
+            "Title must be from 1 to 50 characters",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    Ok(Json(
+        update_person_by_id(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            person_id,
+            changes,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/person/{person_id}/merge",
+    tag = "persons",
+    params(("person_id" = i32, Path, description = "Id of person that receives the faces")),
+    request_body = MergePersons,
+    responses(
+        (status = 200, description = "Merge source person into this one", body = Person),
+        (status = 404, description = "Person not found")
+    )
+)]
+pub async fn post_merge_person(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(person_id): Path<i32>,
+    Json(merge): Json<MergePersons>,
+) -> Result<Json<Person>, Error> {
+    if merge.source_id == person_id {
+        return Err(Error::new(
+            "Can't merge person with itself",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    Ok(Json(
+        merge_persons(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            person_id,
+            merge,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/person/{person_id}",
+    tag = "persons",
+    params(("person_id" = i32, Path, description = "Id of person")),
+    responses(
+        (status = 200, description = "Delete person, its faces become unassigned"),
+        (status = 404, description = "Person not found")
+    )
+)]
+pub async fn delete_person(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(person_id): Path<i32>,
+) -> Result<StatusCode, Error> {
+    delete_person_by_id(&state.pool, Owner::of(&user, &state.config.auth), person_id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/person/search",
+    tag = "persons",
+    params(SimilarPhotosFilters),
+    request_body(content_type="multipart/form-data", content=FaceSearchFormUtopia),
+    responses(
+        (status = 200, description = "Persons and photos with a face similar to the uploaded one", body = FaceSearchResult),
+        (status = 422, description = "No face found on the image")
+    )
+)]
+pub async fn search_by_face(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(filters): Query<SimilarPhotosFilters>,
+    face_form: TypedMultipart<FaceSearchForm>,
+) -> Result<Json<FaceSearchResult>, Error> {
+    let image = face_form.0.face_image.contents.to_vec();
+
+    let face = state
+        .ml
+        .faces_recognition(image)
+        .await?
+        .into_iter()
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+        .ok_or(Error::new(
+            "No face found on the image",
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ))?;
+
+    Ok(Json(
+        search_by_face_embedding(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            Vector::from(face.embedding),
+            filters,
+            state.config.recognition.face_distance_threshold,
+        )
+        .await?,
+    ))
+}
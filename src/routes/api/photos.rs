@@ -1,18 +1,41 @@
-use axum::extract::Query;
-use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use axum::extract::{Extension, State};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_extra::extract::Query;
 use axum_typed_multipart::TypedMultipart;
 
+use pgvector::Vector;
+
 use crate::{
-    models::{ListPhoto, PhotoForm, PhotosFilters},
+    middleware::errors::Error,
+    models::{
+        DuplicateGroup, ListPhoto, Owner, Page, PageParams, PhotoForm, PhotosFilters,
+        ResolveDuplicates, SimilarPhoto, SimilarPhotoForm, SimilarPhotosFilters, UploadedPhoto,
+        User,
+    },
+    services::duplicates::{get_duplicate_groups, resolve_duplicates},
     services::facial_recognition::create_photo,
-    services::photos::{delete_photo_by_id, get_photo_by_id, get_photos_by_filters},
+    services::pagination::PageQuery,
+    services::photos::{
+        delete_photo_by_id, get_photo_by_id, get_photo_embedding, get_photos_by_filters,
+        get_similar_photos,
+    },
+    state::AppState,
 };
 
-pub async fn router() -> Router {
+pub async fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(get_photos).post(post_photo))
         .route("/:photo_id", get(get_photo).delete(delete_photo))
         .route("/search", get(search_by_text))
+        .route("/duplicates", get(get_duplicates))
+        .route("/duplicates/resolve", post(post_resolve_duplicates))
+        .route("/similar", post(search_by_image))
+        .route("/:photo_id/similar", get(get_similar))
 }
 
 #[utoipa::path(
@@ -24,8 +47,13 @@ pub async fn router() -> Router {
         (status = 200, description = "Get photo info", body = ListPhoto)
     )
 )]
-pub async fn get_photo(Path(photo_id): Path<i32>) -> Result<Json<ListPhoto>, StatusCode> {
-    let photo = get_photo_by_id(photo_id).await.unwrap();
+pub async fn get_photo(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(photo_id): Path<i32>,
+) -> Result<Json<ListPhoto>, Error> {
+    let photo =
+        get_photo_by_id(&state.pool, Owner::of(&user, &state.config.auth), photo_id).await?;
     Ok(Json(photo))
 }
 
@@ -35,24 +63,56 @@ pub async fn get_photo(Path(photo_id): Path<i32>) -> Result<Json<ListPhoto>, Sta
     tag = "photos",
     request_body(content_type="multipart/form-data", content=PhotoFormUtopia),
     responses(
-        (status = 201, description = "Add new photo")
+        (status = 202, description = "Photo is stored and queued for processing", body = UploadedPhoto),
+        (status = 400, description = "Unsupported image"),
+        (status = 403, description = "Only album contributors can upload into a shared album"),
+        (status = 404, description = "Album not found"),
+        (status = 409, description = "The same file is already uploaded or album is smart")
     )
 )]
-pub async fn post_photo(photo_form: TypedMultipart<PhotoForm>) {
-    create_photo(photo_form.0, 1).await.unwrap();
+pub async fn post_photo(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    photo_form: TypedMultipart<PhotoForm>,
+) -> Result<(StatusCode, Json<UploadedPhoto>), Error> {
+    let uploaded = create_photo(
+        &state.pool,
+        state.storage.clone(),
+        &state.config,
+        photo_form.0,
+        user.id,
+    )
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(uploaded)))
 }
 
 #[utoipa::path(
     get,
     path = "/api/photo",
     tag = "photos",
-    params(PhotosFilters),
+    params(PhotosFilters, PageParams),
     responses(
-        (status = 200, description = "Get all photos of user", body = Vec<ListPhoto>)
+        (status = 200, description = "Page of photos of user", body = PhotoPage),
+        (status = 400, description = "Invalid cursor")
     )
 )]
-pub async fn get_photos(Query(filters): Query<PhotosFilters>) -> Json<Vec<ListPhoto>> {
-    Json(get_photos_by_filters(filters).await)
+pub async fn get_photos(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(filters): Query<PhotosFilters>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListPhoto>>, Error> {
+    let page = PageQuery::new(&page_params, filters.page_sort())?;
+    Ok(Json(
+        get_photos_by_filters(
+            &state.pool,
+            state.ml.as_ref(),
+            Owner::of(&user, &state.config.auth),
+            filters,
+            page,
+        )
+        .await?,
+    ))
 }
 
 #[utoipa::path(
@@ -64,22 +124,164 @@ pub async fn get_photos(Query(filters): Query<PhotosFilters>) -> Json<Vec<ListPh
         (status = 201, description = "Delete photo")
     )
 )]
-pub async fn delete_photo(Path(photo_id): Path<i32>) -> StatusCode {
-    match delete_photo_by_id(photo_id).await {
-        Ok(()) => StatusCode::OK,
-        Err(code) => code,
-    }
+pub async fn delete_photo(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(photo_id): Path<i32>,
+) -> Result<StatusCode, Error> {
+    delete_photo_by_id(
+        &state.pool,
+        state.storage.as_ref(),
+        Owner::of(&user, &state.config.auth),
+        photo_id,
+    )
+    .await?;
+    Ok(StatusCode::OK)
 }
 
 #[utoipa::path(
     get,
     path = "/api/photo/search",
     tag = "photos",
-    params(PhotosFilters),
+    params(PhotosFilters, PageParams),
+    responses(
+        (status = 200, description = "Search image by text", body = PhotoPage),
+        (status = 400, description = "Invalid cursor")
+    )
+)]
+pub async fn search_by_text(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(filters): Query<PhotosFilters>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListPhoto>>, Error> {
+    let page = PageQuery::new(&page_params, filters.page_sort())?;
+    Ok(Json(
+        get_photos_by_filters(
+            &state.pool,
+            state.ml.as_ref(),
+            Owner::of(&user, &state.config.auth),
+            filters,
+            page,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/photo/{photo_id}/similar",
+    tag = "photos",
+    params(
+        ("photo_id" = i32, Path, description = "Photo id"),
+        SimilarPhotosFilters
+    ),
+    responses(
+        (status = 200, description = "Photos similar to the given one", body = Vec<SimilarPhoto>),
+        (status = 404, description = "Photo not found or not processed yet")
+    )
+)]
+pub async fn get_similar(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(photo_id): Path<i32>,
+    Query(filters): Query<SimilarPhotosFilters>,
+) -> Result<Json<Vec<SimilarPhoto>>, Error> {
+    let embedding =
+        get_photo_embedding(&state.pool, Owner::of(&user, &state.config.auth), photo_id)
+            .await?
+            .ok_or(Error::new(
+                "Photo has no embedding yet",
+                StatusCode::NOT_FOUND,
+            ))?;
+
+    Ok(Json(
+        get_similar_photos(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            embedding,
+            Some(photo_id),
+            filters,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/photo/similar",
+    tag = "photos",
+    params(SimilarPhotosFilters),
+    request_body(content_type="multipart/form-data", content=SimilarPhotoFormUtopia),
+    responses(
+        (status = 200, description = "Photos similar to the uploaded image", body = Vec<SimilarPhoto>)
+    )
+)]
+pub async fn search_by_image(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(filters): Query<SimilarPhotosFilters>,
+    photo_form: TypedMultipart<SimilarPhotoForm>,
+) -> Result<Json<Vec<SimilarPhoto>>, Error> {
+    let image = photo_form.0.photo_image.contents.to_vec();
+    let embedding = Vector::from(state.ml.clip_visual(image).await?);
+
+    Ok(Json(
+        get_similar_photos(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            embedding,
+            None,
+            filters,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/photo/duplicates",
+    tag = "photos",
+    responses(
+        (status = 200, description = "Groups of near-duplicate photos", body = Vec<DuplicateGroup>)
+    )
+)]
+pub async fn get_duplicates(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<DuplicateGroup>>, Error> {
+    Ok(Json(
+        get_duplicate_groups(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            &state.config.duplicates,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/photo/duplicates/resolve",
+    tag = "photos",
+    request_body = ResolveDuplicates,
     responses(
-        (status = 200, description = "Search image by text", body = Vec<ListPhoto>)
+        (status = 200, description = "Keep one photo and delete the others", body = ListPhoto),
+        (status = 404, description = "Photo not found")
     )
 )]
-pub async fn search_by_text(Query(filters): Query<PhotosFilters>) -> Json<Vec<ListPhoto>> {
-    Json(get_photos_by_filters(filters).await)
+pub async fn post_resolve_duplicates(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(resolve): Json<ResolveDuplicates>,
+) -> Result<Json<ListPhoto>, Error> {
+    Ok(Json(
+        resolve_duplicates(
+            &state.pool,
+            state.storage.as_ref(),
+            Owner::of(&user, &state.config.auth),
+            resolve,
+        )
+        .await?,
+    ))
 }
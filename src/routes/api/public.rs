@@ -0,0 +1,244 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, Response, StatusCode},
+    routing::get,
+    Json, Router,
+};
+
+use crate::{
+    errors::{MediaError, ShareLinkError},
+    middleware::errors::Error,
+    models::*,
+    services::media::{
+        serve_media_file, shared_photo_media_path, signed_media_url, verify_media_signature,
+    },
+    services::pagination::PageQuery,
+    services::photos::get_photos_by_filters,
+    services::share_links::{
+        get_shared_original, get_shared_photo, open_share_link, open_signed_share_link,
+        view_share_link,
+    },
+    state::AppState,
+};
+
+/// Заголовок с паролем защищённой ссылки
+const SHARE_PASSWORD_HEADER: &str = "x-share-password";
+
+/// Маршруты без авторизации, доступны только ресурсы ссылки
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/:token", get(get_shared))
+        .route("/:token/photos", get(get_shared_photos))
+        .route(
+            "/:token/photos/:photo_id/original",
+            get(get_shared_photo_original),
+        )
+        .route(
+            "/:token/photos/:photo_id/media/:variant",
+            get(get_shared_photo_media),
+        )
+        .route(
+            "/:token/photos/:photo_id/media/:variant/signed",
+            get(get_shared_photo_media_url),
+        )
+}
+
+fn share_password(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(SHARE_PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/{token}",
+    tag = "sharing",
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("X-Share-Password" = Option<String>, Header, description = "Password of a protected link")
+    ),
+    responses(
+        (status = 200, description = "Shared album or photo, counts a view", body = SharedResource),
+        (status = 401, description = "Share link password is required or wrong"),
+        (status = 404, description = "Share link not found"),
+        (status = 410, description = "Share link has expired"),
+        (status = 429, description = "Too many wrong passwords, the link is locked for a while")
+    )
+)]
+pub async fn get_shared(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<SharedResource>, Error> {
+    let link = open_share_link(&state.pool, token, share_password(&headers)).await?;
+    Ok(Json(view_share_link(&state.pool, link).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/{token}/photos",
+    tag = "sharing",
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("X-Share-Password" = Option<String>, Header, description = "Password of a protected link"),
+        SharedPhotosQuery,
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Page of photos of a shared album", body = PhotoPage),
+        (status = 400, description = "Invalid cursor"),
+        (status = 401, description = "Share link password is required or wrong"),
+        (status = 404, description = "Share link not found or it is not an album link"),
+        (status = 410, description = "Share link has expired"),
+        (status = 429, description = "Too many wrong passwords, the link is locked for a while")
+    )
+)]
+pub async fn get_shared_photos(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SharedPhotosQuery>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<ListPhoto>>, Error> {
+    let link = open_share_link(&state.pool, token, share_password(&headers)).await?;
+    let album_id = link.album_id.ok_or(diesel::result::Error::NotFound)?;
+
+    let filters = PhotosFilters {
+        album_id: Some(album_id),
+        sort: query.sort,
+        ..Default::default()
+    };
+    let page = PageQuery::new(&page_params, filters.page_sort())?;
+
+    Ok(Json(
+        get_photos_by_filters(&state.pool, state.ml.as_ref(), Owner::Any, filters, page).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/{token}/photos/{photo_id}/original",
+    tag = "sharing",
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("photo_id" = i32, Path, description = "Id of shared photo"),
+        ("X-Share-Password" = Option<String>, Header, description = "Password of a protected link")
+    ),
+    responses(
+        (status = 200, description = "Original file of shared photo", content_type = "application/octet-stream"),
+        (status = 401, description = "Share link password is required or wrong"),
+        (status = 403, description = "Downloads are not allowed for this share link"),
+        (status = 404, description = "Share link or photo not found"),
+        (status = 410, description = "Share link has expired"),
+        (status = 429, description = "Too many wrong passwords, the link is locked for a while")
+    )
+)]
+pub async fn get_shared_photo_original(
+    State(state): State<AppState>,
+    Path((token, photo_id)): Path<(String, i32)>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, Error> {
+    let link = open_share_link(&state.pool, token, share_password(&headers)).await?;
+    let original =
+        get_shared_original(&state.pool, state.storage.as_ref(), &link, photo_id).await?;
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, original.mime_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", original.file_name),
+        )
+        .body(Body::from(original.content))
+        .map_err(|err| {
+            log::error!("Response error: {err}");
+            Error::new("Response error", StatusCode::INTERNAL_SERVER_ERROR)
+        })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/{token}/photos/{photo_id}/media/{variant}",
+    tag = "sharing",
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("photo_id" = i32, Path, description = "Id of shared photo"),
+        ("variant" = MediaVariant, Path, description = "Photo file variant"),
+        ("X-Share-Password" = Option<String>, Header, description = "Password of a protected link, not needed with a signature"),
+        SignedMediaQuery
+    ),
+    responses(
+        (status = 200, description = "Shared photo file, supports Range and conditional requests"),
+        (status = 206, description = "Part of shared photo file"),
+        (status = 304, description = "Shared photo file is not modified"),
+        (status = 401, description = "Share link password is required or wrong, or the signature is invalid"),
+        (status = 403, description = "Original files are not allowed for this share link"),
+        (status = 404, description = "Share link, photo or file not found"),
+        (status = 410, description = "Share link has expired"),
+        (status = 429, description = "Too many wrong passwords, the link is locked for a while")
+    )
+)]
+pub async fn get_shared_photo_media(
+    State(state): State<AppState>,
+    Path((token, photo_id, variant)): Path<(String, i32, MediaVariant)>,
+    Query(signed): Query<SignedMediaQuery>,
+    req: Request,
+) -> Result<Response<Body>, Error> {
+    // Подписанный адрес выдаётся после ввода пароля, тегам <img> его не
+    // передать, поэтому с подписью пароль не нужен
+    let link = if signed.signature.is_some() {
+        let path = shared_photo_media_path(&token, photo_id, variant);
+        verify_media_signature(&path, &signed, &state.config)?;
+        open_signed_share_link(&state.pool, token).await?
+    } else {
+        open_share_link(&state.pool, token, share_password(req.headers())).await?
+    };
+    if variant == MediaVariant::Original && !link.allow_download {
+        return Err(ShareLinkError::DownloadForbidden.into());
+    }
+
+    let photo = get_shared_photo(&state.pool, &link, photo_id).await?;
+    let file_path = variant.file_path(photo).ok_or(MediaError::NotFound)?;
+    Ok(serve_media_file(state.storage.as_ref(), &file_path, req, &state.config).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/public/{token}/photos/{photo_id}/media/{variant}/signed",
+    tag = "sharing",
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("photo_id" = i32, Path, description = "Id of shared photo"),
+        ("variant" = MediaVariant, Path, description = "Photo file variant"),
+        ("X-Share-Password" = Option<String>, Header, description = "Password of a protected link")
+    ),
+    responses(
+        (status = 200, description = "Short-lived URL of shared photo file for <img> tags", body = SignedMediaUrl),
+        (status = 401, description = "Share link password is required or wrong"),
+        (status = 403, description = "Original files are not allowed for this share link"),
+        (status = 404, description = "Share link, photo or file not found"),
+        (status = 410, description = "Share link has expired"),
+        (status = 429, description = "Too many wrong passwords, the link is locked for a while")
+    )
+)]
+pub async fn get_shared_photo_media_url(
+    State(state): State<AppState>,
+    Path((token, photo_id, variant)): Path<(String, i32, MediaVariant)>,
+    headers: HeaderMap,
+) -> Result<Json<SignedMediaUrl>, Error> {
+    let path = shared_photo_media_path(&token, photo_id, variant);
+    let link = open_share_link(&state.pool, token, share_password(&headers)).await?;
+    if variant == MediaVariant::Original && !link.allow_download {
+        return Err(ShareLinkError::DownloadForbidden.into());
+    }
+
+    let photo = get_shared_photo(&state.pool, &link, photo_id).await?;
+    let file_path = variant.file_path(photo).ok_or(MediaError::NotFound)?;
+    Ok(Json(signed_media_url(
+        state.storage.as_ref(),
+        &file_path,
+        &path,
+        &state.config,
+    )))
+}
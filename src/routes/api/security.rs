@@ -1,41 +1,194 @@
-use axum::{extract::Json, http::StatusCode};
-use tower_cookies::{Cookie, Cookies};
+use axum::{
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use tower_cookies::{
+    cookie::{time::Duration, SameSite},
+    Cookie, Cookies,
+};
 
 use crate::{
-    middleware::authorize::{encode_jwt, verify_password},
+    config::{AuthConfig, CookieSameSite},
+    errors::AuthError,
+    middleware::authorize::{bearer_token, token_session_id, TOKEN_COOKIE},
+    middleware::errors::Error,
     models::*,
-    services::users::get_user_by_email,
+    services::auth::{
+        get_refresh_token_session, refresh_session, revoke_session, sign_in as sign_in_user,
+        start_session, SessionTokens,
+    },
+    services::users::register_user,
+    state::AppState,
 };
 use axum::{routing::post, Router};
 
-pub async fn router() -> Router {
+/// Cookie с токеном обновления
+const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Токен обновления отправляется только в `/api/auth/refresh` и
+/// `/api/auth/logout`, а не с каждым запросом
+const REFRESH_TOKEN_PATH: &str = "/api/auth";
+
+pub async fn router() -> Router<AppState> {
     Router::new().route("/login/api", post(sign_in))
 }
 
+fn token_cookie(
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    max_age_secs: i64,
+    config: &AuthConfig,
+) -> Cookie<'static> {
+    let same_site = match config.cookie_same_site {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    };
+
+    Cookie::build((name, value))
+        .path(path)
+        .http_only(true)
+        .secure(config.cookie_secure)
+        .same_site(same_site)
+        .max_age(Duration::seconds(max_age_secs))
+        .build()
+}
+
+fn set_session_cookies(cookies: &Cookies, tokens: SessionTokens, config: &AuthConfig) {
+    cookies.add(token_cookie(
+        TOKEN_COOKIE,
+        tokens.access_token,
+        "/",
+        config.token_lifetime_secs,
+        config,
+    ));
+    cookies.add(token_cookie(
+        REFRESH_TOKEN_COOKIE,
+        tokens.refresh_token,
+        REFRESH_TOKEN_PATH,
+        config.refresh_token_lifetime_secs,
+        config,
+    ));
+}
+
+fn clear_session_cookies(cookies: &Cookies, config: &AuthConfig) {
+    cookies.add(token_cookie(TOKEN_COOKIE, String::new(), "/", 0, config));
+    cookies.add(token_cookie(
+        REFRESH_TOKEN_COOKIE,
+        String::new(),
+        REFRESH_TOKEN_PATH,
+        0,
+        config,
+    ));
+}
+
 #[utoipa::path(
     post,
     path = "/api/signin",
+    request_body = SignInData,
     responses(
-        (status = 200, description = "Sign user")
+        (status = 200, description = "Sign user in, access and refresh tokens are set as HttpOnly cookies"),
+        (status = 401, description = "Wrong email or password")
     )
 )]
 pub async fn sign_in(
+    State(state): State<AppState>,
     cookies: Cookies,
     Json(user_data): Json<SignInData>,
-) -> Result<StatusCode, StatusCode> {
-    let user = match get_user_by_email(&user_data.email).await {
-        Some(user) => user,
-        None => return Err(StatusCode::UNAUTHORIZED),
+) -> Result<StatusCode, Error> {
+    let auth = &state.config.auth;
+    let tokens = sign_in_user(&state.pool, auth, &user_data.email, user_data.password).await?;
+
+    set_session_cookies(&cookies, tokens, auth);
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    responses(
+        (status = 204, description = "Refresh token is exchanged for new access and refresh tokens"),
+        (status = 401, description = "Refresh token is missing, invalid, expired or already used")
+    )
+)]
+pub async fn refresh(State(state): State<AppState>, cookies: Cookies) -> Result<StatusCode, Error> {
+    let auth = &state.config.auth;
+    let refresh_token = cookies
+        .get(REFRESH_TOKEN_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or(AuthError::MissingRefreshToken)?;
+
+    match refresh_session(&state.pool, auth, &refresh_token).await {
+        Ok(tokens) => {
+            set_session_cookies(&cookies, tokens, auth);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(err) => {
+            clear_session_cookies(&cookies, auth);
+            Err(err.into())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    params(LogoutQuery),
+    responses(
+        (status = 204, description = "Session is revoked and token cookies are cleared")
+    )
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Query(query): Query<LogoutQuery>,
+) -> Result<StatusCode, Error> {
+    let auth = &state.config.auth;
+
+    // Сессия берётся из токена доступа, а если его cookie уже истекла - из
+    // токена обновления
+    let token = match bearer_token(&headers) {
+        Some(token) => Some(token.to_string()),
+        None => cookies
+            .get(TOKEN_COOKIE)
+            .map(|cookie| cookie.value().to_string()),
+    };
+    let session_id = match token.and_then(|token| token_session_id(&token, auth)) {
+        Some(session_id) => Some(session_id),
+        None => match cookies.get(REFRESH_TOKEN_COOKIE) {
+            Some(cookie) => get_refresh_token_session(&state.pool, cookie.value()).await?,
+            None => None,
+        },
     };
 
-    if !verify_password(&user_data.password, &user.password)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    {
-        return Err(StatusCode::UNAUTHORIZED);
+    if let Some(session_id) = session_id {
+        revoke_session(&state.pool, session_id, query.all).await?;
     }
+    clear_session_cookies(&cookies, auth);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let token = encode_jwt(user.email).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+#[utoipa::path(
+    post,
+    path = "/api/register",
+    request_body = RegisterUser,
+    responses(
+        (status = 200, description = "Register user by invite code and sign in, tokens are set as HttpOnly cookies", body = User),
+        (status = 400, description = "Invalid username, email, password or invite code"),
+        (status = 403, description = "Self-registration is disabled"),
+        (status = 409, description = "Username or email is already taken")
+    )
+)]
+pub async fn register(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(registration): Json<RegisterUser>,
+) -> Result<Json<User>, Error> {
+    let auth = &state.config.auth;
+    let user = register_user(&state.pool, auth, registration).await?;
+    let tokens = start_session(&state.pool, auth, user.clone()).await?;
 
-    cookies.add(Cookie::new("token", token));
-    Ok(StatusCode::OK)
+    set_session_cookies(&cookies, tokens, auth);
+    Ok(Json(user))
 }
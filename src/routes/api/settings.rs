@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    routing::{delete, get, patch, put},
+    Json, Router,
+};
+use axum_typed_multipart::TypedMultipart;
+
+use crate::{
+    middleware::{authorize::Credentials, errors::Error},
+    models::*,
+    services::api_keys::{create_api_key, delete_api_key, get_api_keys},
+    services::users::{change_password, delete_avatar, set_avatar, update_profile},
+    state::AppState,
+};
+
+/// Настройки текущего пользователя, доступны только из сессии
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/profile", patch(patch_profile))
+        .route("/password", put(put_password))
+        .route("/avatar", put(put_avatar).delete(delete_avatar_image))
+        .route("/api-keys", get(get_keys).post(post_key))
+        .route("/api-keys/:key_id", delete(delete_key))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/settings/profile",
+    tag = "settings",
+    request_body = UpdateProfile,
+    responses(
+        (status = 200, description = "Update profile of current user", body = User),
+        (status = 400, description = "Invalid username"),
+        (status = 409, description = "Username is already taken")
+    )
+)]
+pub async fn patch_profile(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(profile): Json<UpdateProfile>,
+) -> Result<Json<User>, Error> {
+    Ok(Json(update_profile(&state.pool, user.id, profile).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/settings/password",
+    tag = "settings",
+    request_body = ChangePassword,
+    responses(
+        (status = 204, description = "Password is changed, other sessions of user are revoked"),
+        (status = 400, description = "New password is too short"),
+        (status = 403, description = "Current password is wrong")
+    )
+)]
+pub async fn put_password(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(credentials): Extension<Credentials>,
+    Json(change): Json<ChangePassword>,
+) -> Result<StatusCode, Error> {
+    let session_id = credentials.session_id().map(str::to_string);
+    change_password(&state.pool, user, session_id, change).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/settings/avatar",
+    tag = "settings",
+    request_body(content_type="multipart/form-data", content=AvatarFormUtopia),
+    responses(
+        (status = 200, description = "Upload avatar of current user", body = User),
+        (status = 400, description = "Unsupported image")
+    )
+)]
+pub async fn put_avatar(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    avatar_form: TypedMultipart<AvatarForm>,
+) -> Result<Json<User>, Error> {
+    let content = avatar_form.0.avatar_image.contents.to_vec();
+    Ok(Json(
+        set_avatar(
+            &state.pool,
+            state.storage.as_ref(),
+            &state.config,
+            user.id,
+            content,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/settings/avatar",
+    tag = "settings",
+    responses(
+        (status = 200, description = "Remove avatar of current user", body = User)
+    )
+)]
+pub async fn delete_avatar_image(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<User>, Error> {
+    Ok(Json(
+        delete_avatar(&state.pool, state.storage.as_ref(), user.id).await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settings/api-keys",
+    tag = "settings",
+    responses(
+        (status = 200, description = "Personal API keys of current user", body = [ApiKey])
+    )
+)]
+pub async fn get_keys(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ApiKey>>, Error> {
+    Ok(Json(get_api_keys(&state.pool, user.id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/api-keys",
+    tag = "settings",
+    request_body = NewApiKey,
+    responses(
+        (status = 200, description = "Create personal API key, the key itself is returned only once", body = CreatedApiKey),
+        (status = 400, description = "Invalid key name or expiry"),
+        (status = 403, description = "Request is authorized with an API key")
+    )
+)]
+pub async fn post_key(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(new_key): Json<NewApiKey>,
+) -> Result<Json<CreatedApiKey>, Error> {
+    Ok(Json(create_api_key(&state.pool, user.id, new_key).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/settings/api-keys/{key_id}",
+    tag = "settings",
+    params(("key_id" = i32, Path, description = "Id of API key")),
+    responses(
+        (status = 200, description = "Revoke personal API key"),
+        (status = 403, description = "Request is authorized with an API key"),
+        (status = 404, description = "API key not found")
+    )
+)]
+pub async fn delete_key(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(key_id): Path<i32>,
+) -> Result<(), Error> {
+    delete_api_key(&state.pool, user.id, key_id).await?;
+    Ok(())
+}
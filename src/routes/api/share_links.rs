@@ -0,0 +1,81 @@
+use axum::{
+    extract::{Extension, Path, State},
+    routing::{delete, get},
+    Json, Router,
+};
+
+use crate::{
+    middleware::errors::Error,
+    models::*,
+    services::share_links::{create_share_link, delete_share_link, get_share_links},
+    state::AppState,
+};
+
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_links).post(post_link))
+        .route("/:link_id", delete(delete_link))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/share",
+    tag = "sharing",
+    responses(
+        (status = 200, description = "Public share links of user", body = [ShareLink])
+    )
+)]
+pub async fn get_links(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ShareLink>>, Error> {
+    Ok(Json(
+        get_share_links(&state.pool, Owner::of(&user, &state.config.auth)).await?,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/share",
+    tag = "sharing",
+    request_body = NewShareLink,
+    responses(
+        (status = 200, description = "Create public share link for an album or a photo", body = ShareLink),
+        (status = 400, description = "Invalid share link target or expiry"),
+        (status = 404, description = "Album or photo not found")
+    )
+)]
+pub async fn post_link(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(new_link): Json<NewShareLink>,
+) -> Result<Json<ShareLink>, Error> {
+    Ok(Json(
+        create_share_link(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            user.id,
+            new_link,
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/share/{link_id}",
+    tag = "sharing",
+    params(("link_id" = i32, Path, description = "Id of share link")),
+    responses(
+        (status = 200, description = "Revoke share link"),
+        (status = 404, description = "Share link not found")
+    )
+)]
+pub async fn delete_link(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(link_id): Path<i32>,
+) -> Result<(), Error> {
+    delete_share_link(&state.pool, Owner::of(&user, &state.config.auth), link_id).await?;
+    Ok(())
+}
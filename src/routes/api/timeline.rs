@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Extension, State},
+    routing::get,
+    Json, Router,
+};
+use axum_extra::extract::Query;
+
+use crate::{
+    middleware::errors::Error,
+    models::*,
+    services::timeline::{get_timeline, get_timeline_photos},
+    state::AppState,
+};
+
+pub async fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_buckets))
+        .route("/photos", get(get_bucket_photos))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/timeline",
+    tag = "timeline",
+    params(TimelineQuery),
+    responses(
+        (status = 200, description = "Photo counts by capture period, newest first", body = Vec<TimelineBucket>)
+    )
+)]
+pub async fn get_buckets(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<TimelineQuery>,
+) -> Result<Json<Vec<TimelineBucket>>, Error> {
+    Ok(Json(
+        get_timeline(
+            &state.pool,
+            Owner::of(&user, &state.config.auth),
+            params.granularity.unwrap_or_default(),
+        )
+        .await?,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/timeline/photos",
+    tag = "timeline",
+    params(TimelinePhotosQuery),
+    responses(
+        (status = 200, description = "Page of photos from one capture period, newest first", body = Vec<ListPhoto>)
+    )
+)]
+pub async fn get_bucket_photos(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<TimelinePhotosQuery>,
+) -> Result<Json<Vec<ListPhoto>>, Error> {
+    Ok(Json(
+        get_timeline_photos(&state.pool, Owner::of(&user, &state.config.auth), params).await?,
+    ))
+}
@@ -1,8 +1,7 @@
 use axum::{
-    extract::{Extension, Path, Query},
-    http::StatusCode,
+    extract::{Extension, Path, Query, State},
     middleware,
-    routing::get,
+    routing::{delete, get},
     Json, Router,
 };
 
@@ -10,10 +9,15 @@ use crate::{
     middleware::admin_permissions,
     middleware::errors::Error,
     models::*,
-    services::users::{create_user, delete_user_by_id, get_user_by_id, get_users_with_filters},
+    services::pagination::PageQuery,
+    services::users::{
+        create_invite, create_user, delete_invite, delete_user_by_id, get_invites, get_user_by_id,
+        get_users_with_filters,
+    },
+    state::AppState,
 };
 
-pub async fn router() -> Router {
+pub async fn router() -> Router<AppState> {
     Router::new()
         .route(
             "/:user_id",
@@ -27,6 +31,17 @@ pub async fn router() -> Router {
                 .post(post_user)
                 .layer(middleware::from_fn(admin_permissions::admin_permissions)),
         )
+        .route(
+            "/invites",
+            get(get_user_invites)
+                .post(post_invite)
+                .layer(middleware::from_fn(admin_permissions::admin_permissions)),
+        )
+        .route(
+            "/invites/:invite_id",
+            delete(delete_user_invite)
+                .layer(middleware::from_fn(admin_permissions::admin_permissions)),
+        )
         .route("/current_user", get(get_current_user))
 }
 
@@ -39,23 +54,32 @@ pub async fn router() -> Router {
         (status = 201, description = "Create user account", body = User)
     )
 )]
-pub async fn post_user(Json(new_user): Json<NewUser>) -> Json<User> {
-    let user = create_user(new_user).await;
-    Json(user)
+pub async fn post_user(
+    State(state): State<AppState>,
+    Json(new_user): Json<NewUser>,
+) -> Result<Json<User>, Error> {
+    let user = create_user(&state.pool, new_user).await?;
+    Ok(Json(user))
 }
 
 #[utoipa::path(
     get,
     path = "/api/user",
     tag = "users",
-    params(UsersQuery),
+    params(UsersQuery, PageParams),
     responses(
-        (status = 201, description = "Create user account", body = Vec<User>)
+        (status = 200, description = "Page of user accounts", body = UserPage),
+        (status = 400, description = "Invalid cursor")
     )
 )]
-pub async fn get_users(Query(params): Query<UsersQuery>) -> Json<Vec<User>> {
-    let users = get_users_with_filters(params).await;
-    Json(users)
+pub async fn get_users(
+    State(state): State<AppState>,
+    Query(params): Query<UsersQuery>,
+    Query(page_params): Query<PageParams>,
+) -> Result<Json<Page<User>>, Error> {
+    let page = PageQuery::new(&page_params, Some(params.sort.unwrap_or_default()))?;
+    let users = get_users_with_filters(&state.pool, params, page).await?;
+    Ok(Json(users))
 }
 
 #[utoipa::path(
@@ -64,11 +88,16 @@ pub async fn get_users(Query(params): Query<UsersQuery>) -> Json<Vec<User>> {
     tag = "users",
     params(("user_id" = i32, Path, description = "Todo database id")),
     responses(
-        (status = 201, description = "Create user account")
+        (status = 200, description = "Delete user account with its photos, albums and persons"),
+        (status = 404, description = "User not found")
     )
 )]
-pub async fn delete_user(Path(user_id): Path<i32>) {
-    delete_user_by_id(user_id).await;
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> Result<(), Error> {
+    delete_user_by_id(&state.pool, state.storage.as_ref(), user_id).await?;
+    Ok(())
 }
 
 #[utoipa::path(
@@ -80,11 +109,12 @@ pub async fn delete_user(Path(user_id): Path<i32>) {
         (status = 200, description = "Detail info about user", body = User)
     )
 )]
-pub async fn get_user(Path(user_id): Path<i32>) -> Result<Json<User>, Error> {
-    match get_user_by_id(user_id).await {
-        Ok(user) => Ok(Json(user)),
-        Err(_) => Err(Error::new("User not found", StatusCode::NOT_FOUND)),
-    }
+pub async fn get_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<i32>,
+) -> Result<Json<User>, Error> {
+    let user = get_user_by_id(&state.pool, user_id).await?;
+    Ok(Json(user))
 }
 
 #[utoipa::path(
@@ -98,3 +128,51 @@ pub async fn get_user(Path(user_id): Path<i32>) -> Result<Json<User>, Error> {
 pub async fn get_current_user(Extension(curr_user): Extension<User>) -> Json<User> {
     Json(curr_user.clone())
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/user/invites",
+    tag = "users",
+    responses(
+        (status = 200, description = "Registration invites", body = [Invite])
+    )
+)]
+pub async fn get_user_invites(State(state): State<AppState>) -> Result<Json<Vec<Invite>>, Error> {
+    Ok(Json(get_invites(&state.pool).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/invites",
+    tag = "users",
+    request_body = NewInvite,
+    responses(
+        (status = 200, description = "Create single-use registration invite", body = Invite),
+        (status = 400, description = "Invalid email or expiry")
+    )
+)]
+pub async fn post_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(new_invite): Json<NewInvite>,
+) -> Result<Json<Invite>, Error> {
+    Ok(Json(create_invite(&state.pool, user.id, new_invite).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/user/invites/{invite_id}",
+    tag = "users",
+    params(("invite_id" = i32, Path, description = "Id of invite")),
+    responses(
+        (status = 200, description = "Revoke registration invite"),
+        (status = 404, description = "Invite not found")
+    )
+)]
+pub async fn delete_user_invite(
+    State(state): State<AppState>,
+    Path(invite_id): Path<i32>,
+) -> Result<(), Error> {
+    delete_invite(&state.pool, invite_id).await?;
+    Ok(())
+}
@@ -1,16 +1,20 @@
 use crate::models::*;
-use crate::routes::api::{albums, photos, security, users};
+use crate::routes::api::{
+    albums, faces, jobs, media, persons, photos, public, security, settings, share_links, timeline,
+    users,
+};
+use crate::state::AppState;
 use api::api_router;
 use axum::extract::DefaultBodyLimit;
 use axum::Router;
 use tower_cookies::CookieManagerLayer;
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use tower_http::cors::CorsLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 pub mod api;
 
-pub async fn craete_app() -> Router {
+pub async fn craete_app(state: AppState) -> Router {
     #[derive(OpenApi)]
     #[openapi(
         paths(
@@ -20,36 +24,117 @@ pub async fn craete_app() -> Router {
             users::delete_user,
             users::get_user,
             users::get_current_user,
+            users::get_user_invites,
+            users::post_invite,
+            users::delete_user_invite,
 
             photos::post_photo,
             photos::get_photo,
             photos::get_photos,
             photos::delete_photo,
             photos::search_by_text,
+            photos::get_similar,
+            photos::search_by_image,
+            photos::get_duplicates,
+            photos::post_resolve_duplicates,
 
             albums::get_album,
             albums::delete_album,
             albums::post_album,
             albums::get_albums,
+            albums::get_album_photos,
+            albums::post_album_photos,
+            albums::delete_album_photos,
+            albums::put_album_cover,
+            albums::put_album_rule,
+            albums::delete_album_rule,
+            albums::post_album_refresh,
+            albums::get_shares,
+            albums::put_share,
+            albums::delete_share,
 
-            security::sign_in
+            share_links::get_links,
+            share_links::post_link,
+            share_links::delete_link,
+            public::get_shared,
+            public::get_shared_photos,
+            public::get_shared_photo_original,
+            public::get_shared_photo_media,
+            public::get_shared_photo_media_url,
+            media::get_photo_media,
+            media::get_photo_media_url,
+            media::get_face_media,
+            media::get_face_media_url,
+            media::get_avatar_media,
+            media::get_avatar_media_url,
+
+            persons::get_persons,
+            persons::get_person,
+            persons::patch_person,
+            persons::post_merge_person,
+            persons::delete_person,
+            persons::search_by_face,
+
+            faces::get_faces_list,
+            faces::get_face,
+            faces::post_face_person,
+            faces::post_detach_face,
+            faces::post_ignore_face,
+            faces::delete_ignore_face,
+
+            jobs::get_job,
+            jobs::post_retry_job,
+
+            timeline::get_buckets,
+            timeline::get_bucket_photos,
+
+            security::sign_in,
+            security::refresh,
+            security::logout,
+            security::register,
+
+            settings::patch_profile,
+            settings::put_password,
+            settings::put_avatar,
+            settings::delete_avatar_image,
+            settings::get_keys,
+            settings::post_key,
+            settings::delete_key
         ),
         components(
-            schemas(NewUser, User, UsersQuery, SignInData, PhotoFormUtopia, Photo, ListPhoto, Album, NewAlbum)
+            schemas(NewUser, User, UsersQuery, SignInData, LogoutQuery, PhotoFormUtopia, Photo, ListPhoto,
+                PhotoExif, PhotoRenditions,
+                SimilarPhotoFormUtopia, SimilarPhotosFilters, SimilarPhoto, Album, NewAlbum,
+                Person, ListPerson, PersonDetail, UpdatePerson, MergePersons, ListFace, MoveFace,
+                FaceSearchFormUtopia, MatchedPerson, FaceSearchResult,
+                Job, JobKind, JobStatus, UploadedPhoto, TimelineGranularity, TimelineBucket,
+                SortDirection, UserSort, AlbumSort, PhotoSort, PersonSort, FaceSort,
+                UserPage, AlbumPage, PhotoPage, PersonPage, FacePage, DuplicateGroup, ResolveDuplicates,
+                AlbumPhotos, AlbumCover, AlbumRule, AlbumRole, AlbumShare, ShareAlbum,
+                ShareLink, NewShareLink, SharedResource, SharedPhotosQuery, MediaVariant, SignedMediaQuery, SignedMediaUrl,
+                ApiKey, ApiKeyScope, NewApiKey, CreatedApiKey, RegisterUser, UpdateProfile,
+                ChangePassword, AvatarFormUtopia, Invite, NewInvite)
         ),
         tags(
             (name = "users", description = "Управление пользователями"),
             (name = "albums", description = "Управление альбомами"),
-            (name = "photos", description = "Управления фотографиями")
+            (name = "photos", description = "Управления фотографиями"),
+            (name = "persons", description = "Управление личностями"),
+            (name = "faces", description = "Исправление распознанных лиц"),
+            (name = "jobs", description = "Фоновая обработка фотографий"),
+            (name = "timeline", description = "Просмотр фотографий по датам съёмки"),
+            (name = "sharing", description = "Публичные ссылки на альбомы и фотографии"),
+            (name = "media", description = "Файлы фотографий и лиц с проверкой доступа"),
+            (name = "settings", description = "Настройки текущего пользователя")
         )
     )]
     struct ApiDoc;
 
     Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        .nest("/api", api_router().await)
-        .nest_service("/storage", ServeDir::new("storage"))
+        .nest("/api", api_router(state.clone()).await)
         .layer(CookieManagerLayer::new())
-        .layer(DefaultBodyLimit::max(100000000))
+        .layer(DefaultBodyLimit::max(state.config.server.body_limit))
         .layer(CorsLayer::permissive())
+        .with_state(state)
 }
@@ -1,5 +1,50 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        #[max_length = 20]
+        scope -> Varchar,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    album_photos (album_id, photo_id) {
+        album_id -> Int4,
+        photo_id -> Int4,
+        added_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    album_shares (album_id, user_id) {
+        album_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 20]
+        role -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use pgvector::sql_types::*;
@@ -8,6 +53,10 @@ diesel::table! {
         id -> Int4,
         #[max_length = 50]
         title -> Varchar,
+        user_id -> Int4,
+        cover_photo_id -> Nullable<Int4>,
+        rule -> Nullable<Jsonb>,
+        rule_embedding -> Nullable<Vector>,
     }
 }
 
@@ -22,6 +71,57 @@ diesel::table! {
         embedding -> Nullable<Vector>,
         path -> Nullable<Text>,
         bbox -> Nullable<Array<Nullable<Int4>>>,
+        is_ignored -> Bool,
+        is_manual -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    face_rejections (face_id, person_id) {
+        face_id -> Int4,
+        person_id -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    invites (id) {
+        id -> Int4,
+        #[max_length = 64]
+        code -> Varchar,
+        created_by -> Int4,
+        #[max_length = 50]
+        email -> Nullable<Varchar>,
+        expires_at -> Nullable<Timestamptz>,
+        used_by -> Nullable<Int4>,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    jobs (id) {
+        id -> Int4,
+        #[max_length = 50]
+        kind -> Varchar,
+        photo_id -> Nullable<Int4>,
+        #[max_length = 20]
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamptz,
+        locked_at -> Nullable<Timestamptz>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -34,6 +134,7 @@ diesel::table! {
         #[max_length = 50]
         title -> Varchar,
         avatar -> Text,
+        user_id -> Int4,
     }
 }
 
@@ -48,7 +149,69 @@ diesel::table! {
         title -> Nullable<Varchar>,
         embedding -> Nullable<Vector>,
         user_id -> Int4,
+        taken_at -> Nullable<Timestamp>,
+        #[max_length = 100]
+        camera_make -> Nullable<Varchar>,
+        #[max_length = 100]
+        camera_model -> Nullable<Varchar>,
+        #[max_length = 100]
+        lens_model -> Nullable<Varchar>,
+        exposure_time -> Nullable<Float8>,
+        f_number -> Nullable<Float8>,
+        iso -> Nullable<Int4>,
+        focal_length -> Nullable<Float8>,
+        orientation -> Nullable<Int2>,
+        latitude -> Nullable<Float8>,
+        longitude -> Nullable<Float8>,
+        original_path -> Nullable<Text>,
+        #[max_length = 100]
+        mime_type -> Nullable<Varchar>,
+        thumbnail_path -> Nullable<Text>,
+        preview_path -> Nullable<Text>,
+        thumbnail_webp_path -> Nullable<Text>,
+        preview_webp_path -> Nullable<Text>,
+        #[max_length = 64]
+        content_hash -> Nullable<Varchar>,
+        perceptual_hash -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    refresh_tokens (id) {
+        id -> Int4,
+        #[max_length = 64]
+        session_id -> Varchar,
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    share_links (id) {
+        id -> Int4,
+        #[max_length = 64]
+        token -> Varchar,
+        user_id -> Int4,
         album_id -> Nullable<Int4>,
+        photo_id -> Nullable<Int4>,
+        #[max_length = 255]
+        password -> Nullable<Varchar>,
+        allow_download -> Bool,
+        expires_at -> Nullable<Timestamptz>,
+        view_count -> Int4,
+        created_at -> Timestamptz,
+        failed_attempts -> Int4,
+        locked_until -> Nullable<Timestamptz>,
     }
 }
 
@@ -69,15 +232,36 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(album_photos -> albums (album_id));
+diesel::joinable!(album_photos -> photos (photo_id));
+diesel::joinable!(album_shares -> albums (album_id));
+diesel::joinable!(album_shares -> users (user_id));
+diesel::joinable!(albums -> users (user_id));
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(face_rejections -> faces (face_id));
+diesel::joinable!(face_rejections -> persons (person_id));
 diesel::joinable!(faces -> persons (person_id));
 diesel::joinable!(faces -> photos (photo_id));
-diesel::joinable!(photos -> albums (album_id));
+diesel::joinable!(jobs -> photos (photo_id));
+diesel::joinable!(persons -> users (user_id));
 diesel::joinable!(photos -> users (user_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(share_links -> albums (album_id));
+diesel::joinable!(share_links -> photos (photo_id));
+diesel::joinable!(share_links -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    album_photos,
+    album_shares,
     albums,
+    api_keys,
+    face_rejections,
     faces,
+    invites,
+    jobs,
     persons,
     photos,
+    refresh_tokens,
+    share_links,
     users,
 );
@@ -1,42 +1,587 @@
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use std::collections::HashSet;
+
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, QueryDsl, QueryResult, RunQueryDsl, SelectableHelper,
+};
+use pgvector::Vector;
 
 use crate::{
-    db_connection::connection,
-    models::{Album, NewAlbum},
+    db_connection::{interact, interact_with, DbPool},
+    errors::{AlbumError, DbError},
+    ml::MlBackend,
+    models::{Album, AlbumRole, AlbumRule, AlbumShare, AlbumSort, NewAlbum, Owner, Page},
+    services::{pagination::PageQuery, photos::filtered_photos},
 };
 
-pub async fn get_album_by_id(album_id: i32) -> Album {
+pub async fn get_album_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+) -> Result<Album, AlbumError> {
+    interact_with(pool, move |conn| {
+        find_album_for(conn, owner, album_id, AlbumRole::Viewer)
+    })
+    .await
+}
+
+pub async fn delete_album_by_id(pool: &DbPool, owner: Owner, album_id: i32) -> Result<(), DbError> {
     use crate::schema::albums::dsl::*;
 
-    albums
+    let mut query = diesel::delete(albums.find(album_id)).into_boxed();
+    if let Some(owner_id) = owner.user_id() {
+        query = query.filter(user_id.eq(owner_id));
+    }
+
+    interact(pool, move |conn| match query.execute(conn)? {
+        0 => Err(diesel::result::Error::NotFound),
+        _ => Ok(()),
+    })
+    .await
+}
+
+pub async fn create_album(
+    pool: &DbPool,
+    ml: &dyn MlBackend,
+    new_album: NewAlbum,
+    uid: i32,
+) -> Result<Album, AlbumError> {
+    use crate::schema::albums;
+
+    let embedding = match &new_album.rule {
+        Some(rule) => embed_rule(ml, rule).await?,
+        None => None,
+    };
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            let album_id: i32 = diesel::insert_into(albums::table)
+                .values((
+                    &new_album,
+                    albums::user_id.eq(uid),
+                    albums::rule_embedding.eq(embedding),
+                ))
+                .returning(albums::id)
+                .get_result(conn)?;
+            sync_smart_album(conn, album_id, None)?;
+
+            Ok(find_album(conn, Owner::User(uid), album_id)?)
+        })
+    })
+    .await
+}
+
+/// Задаёт правило умного альбома и сразу подбирает под него фотографии.
+/// Без правила альбом становится обычным и сохраняет текущие фотографии.
+pub async fn set_album_rule(
+    pool: &DbPool,
+    ml: &dyn MlBackend,
+    owner: Owner,
+    album_id: i32,
+    rule: Option<AlbumRule>,
+) -> Result<Album, AlbumError> {
+    use crate::schema::albums;
+
+    interact_with(pool, move |conn| {
+        find_album_for(conn, owner, album_id, AlbumRole::Editor)
+    })
+    .await?;
+    let embedding = match &rule {
+        Some(rule) => embed_rule(ml, rule).await?,
+        None => None,
+    };
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            find_album_for(conn, owner, album_id, AlbumRole::Editor)?;
+
+            diesel::update(albums::table.find(album_id))
+                .set((albums::rule.eq(rule), albums::rule_embedding.eq(embedding)))
+                .execute(conn)?;
+            sync_smart_album(conn, album_id, None)?;
+
+            Ok(find_album(conn, Owner::Any, album_id)?)
+        })
+    })
+    .await
+}
+
+/// Заново подбирает фотографии умного альбома по его правилу
+pub async fn refresh_album(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+) -> Result<Album, AlbumError> {
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            find_album_for(conn, owner, album_id, AlbumRole::Editor)?;
+            sync_smart_album(conn, album_id, None)?;
+            Ok(find_album(conn, Owner::Any, album_id)?)
+        })
+    })
+    .await
+}
+
+/// Добавляет только что обработанное изображение в подходящие умные альбомы
+/// его владельца
+pub fn refresh_smart_albums_for_photo(conn: &mut PgConnection, photo_id: i32) -> QueryResult<()> {
+    use crate::schema::{albums, photos};
+
+    let user_id: i32 = photos::table
+        .find(photo_id)
+        .select(photos::user_id)
+        .first(conn)?;
+    let album_ids: Vec<i32> = albums::table
+        .filter(albums::user_id.eq(user_id))
+        .filter(albums::rule.is_not_null())
+        .select(albums::id)
+        .load(conn)?;
+
+    for album_id in album_ids {
+        sync_smart_album(conn, album_id, Some(photo_id))?;
+    }
+    Ok(())
+}
+
+/// Приводит состав умного альбома в соответствие с правилом: все фотографии
+/// или только `photo_id`. Обычные альбомы не меняются.
+fn sync_smart_album(
+    conn: &mut PgConnection,
+    album_id: i32,
+    photo_id: Option<i32>,
+) -> QueryResult<()> {
+    use crate::schema::{album_photos, albums, photos};
+
+    let (user_id, rule, embedding): (i32, Option<AlbumRule>, Option<Vector>) = albums::table
         .find(album_id)
-        .select(Album::as_select())
-        .first(&mut connection())
-        .unwrap()
+        .select((albums::user_id, albums::rule, albums::rule_embedding))
+        .first(conn)?;
+    let Some(rule) = rule else {
+        return Ok(());
+    };
+
+    let mut matching = filtered_photos(Owner::User(user_id), &rule.filters(), embedding.as_ref())
+        .select(photos::id);
+    let mut stale = diesel::delete(album_photos::table)
+        .filter(album_photos::album_id.eq(album_id))
+        .into_boxed();
+    if let Some(photo_id) = photo_id {
+        matching = matching.filter(photos::id.eq(photo_id));
+        stale = stale.filter(album_photos::photo_id.eq(photo_id));
+    }
+    let photo_ids: Vec<i32> = matching.load(conn)?;
+
+    stale
+        .filter(album_photos::photo_id.ne_all(&photo_ids))
+        .execute(conn)?;
+    let rows: Vec<_> = photo_ids
+        .into_iter()
+        .map(|photo_id| {
+            (
+                album_photos::album_id.eq(album_id),
+                album_photos::photo_id.eq(photo_id),
+            )
+        })
+        .collect();
+    diesel::insert_into(album_photos::table)
+        .values(rows)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    diesel::update(albums::table.find(album_id))
+        .filter(
+            albums::cover_photo_id.ne_all(
+                album_photos::table
+                    .select(album_photos::photo_id.nullable())
+                    .filter(album_photos::album_id.eq(album_id)),
+            ),
+        )
+        .set(albums::cover_photo_id.eq(None::<i32>))
+        .execute(conn)?;
+
+    Ok(())
 }
 
-pub async fn delete_album_by_id(album_id: i32) {
-    use crate::schema::albums::dsl::*;
+/// Проверяет правило и считает CLIP эмбеддинг его текста
+async fn embed_rule(ml: &dyn MlBackend, rule: &AlbumRule) -> Result<Option<Vector>, AlbumError> {
+    let location = [rule.latitude, rule.longitude, rule.radius_km];
+    if location.iter().any(Option::is_some) && !location.iter().all(Option::is_some) {
+        return Err(AlbumError::InvalidRule(
+            "latitude, longitude and radius_km must be set together",
+        ));
+    }
+    if rule.radius_km.is_some_and(|radius_km| radius_km <= 0.0) {
+        return Err(AlbumError::InvalidRule("radius_km must be positive"));
+    }
+    if rule
+        .max_distance
+        .is_some_and(|max_distance| max_distance < 0.0)
+    {
+        return Err(AlbumError::InvalidRule("max_distance must not be negative"));
+    }
 
-    diesel::delete(albums.filter(id.eq(album_id)))
-        .execute(&mut connection())
-        .expect("Error deleting posts");
+    match &rule.text {
+        Some(text) => Ok(Some(Vector::from(ml.clip_textual(text).await?))),
+        None => Ok(None),
+    }
 }
 
-pub async fn create_album(new_album: NewAlbum) -> Album {
-    diesel::insert_into(crate::schema::albums::table)
-        .values(&new_album)
-        .returning(Album::as_returning())
-        .get_result(&mut connection())
-        .expect("Error saving new post")
+/// Альбомы пользователя `user_id` вместе с альбомами, которыми с ним
+/// поделились, а администратору с `Owner::Any` - все альбомы. `shared`
+/// оставляет только чужие или только собственные альбомы пользователя.
+pub async fn get_albums_with_filters(
+    pool: &DbPool,
+    owner: Owner,
+    user_id: i32,
+    shared: Option<bool>,
+    page: PageQuery<AlbumSort>,
+) -> Result<Page<Album>, DbError> {
+    use crate::schema::{album_shares, albums};
+
+    let filtered = move || {
+        let own = albums::user_id.eq(user_id);
+        let shared_with = albums::id.eq_any(
+            album_shares::table
+                .select(album_shares::album_id)
+                .filter(album_shares::user_id.eq(user_id)),
+        );
+
+        let query = albums::table.into_boxed();
+        match (shared, owner) {
+            (Some(true), _) => query.filter(shared_with),
+            (Some(false), _) => query.filter(own),
+            (None, Owner::Any) => query,
+            (None, Owner::User(_)) => query.filter(own.or(shared_with)),
+        }
+    };
+
+    interact(pool, move |conn| {
+        let total = filtered().count().get_result(conn)?;
+
+        let mut query = filtered()
+            .select((Album::as_select(), page.key()))
+            .order(page.order())
+            .offset(page.offset())
+            .limit(page.fetch_limit());
+        if let Some(after) = page.after() {
+            query = query.filter(after);
+        }
+
+        Ok(page.finish(query.load(conn)?, total, |album| album.id))
+    })
+    .await
 }
 
-pub async fn get_albums_with_filters() -> Vec<Album> {
-    use crate::schema::albums::dsl::*;
+/// Добавляет в альбом фотографии того, кто их добавляет, а администратор -
+/// фотографии владельца альбома. Уже добавленные пропускаются.
+pub async fn add_photos_to_album(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+    photo_ids: Vec<i32>,
+) -> Result<Album, AlbumError> {
+    use crate::schema::{album_photos, photos};
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            let album = find_album_for(conn, owner, album_id, AlbumRole::Contributor)?;
+            if album.rule.is_some() {
+                return Err(AlbumError::SmartAlbum);
+            }
+
+            // Участник не может добавить фотографии владельца: через альбом
+            // они стали бы видны всем, кому он доступен
+            let photos_owner = owner.user_id().unwrap_or(album.user_id);
+            let owned: Vec<i32> = photos::table
+                .filter(photos::id.eq_any(&photo_ids))
+                .filter(photos::user_id.eq(photos_owner))
+                .select(photos::id)
+                .load(conn)?;
+            if owned.len() != photo_ids.iter().collect::<HashSet<_>>().len() {
+                return Err(diesel::result::Error::NotFound.into());
+            }
+
+            let rows: Vec<_> = owned
+                .into_iter()
+                .map(|photo_id| {
+                    (
+                        album_photos::album_id.eq(album_id),
+                        album_photos::photo_id.eq(photo_id),
+                    )
+                })
+                .collect();
+            diesel::insert_into(album_photos::table)
+                .values(rows)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
 
-    albums
-        .limit(5)
+            Ok(find_album(conn, Owner::Any, album_id)?)
+        })
+    })
+    .await
+}
+
+/// Убирает фотографии из альбома, сами фотографии не удаляются
+pub async fn remove_photos_from_album(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+    photo_ids: Vec<i32>,
+) -> Result<Album, AlbumError> {
+    use crate::schema::{album_photos, albums};
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            if find_album_for(conn, owner, album_id, AlbumRole::Editor)?
+                .rule
+                .is_some()
+            {
+                return Err(AlbumError::SmartAlbum);
+            }
+
+            diesel::delete(
+                album_photos::table
+                    .filter(album_photos::album_id.eq(album_id))
+                    .filter(album_photos::photo_id.eq_any(&photo_ids)),
+            )
+            .execute(conn)?;
+
+            diesel::update(albums::table.find(album_id))
+                .filter(albums::cover_photo_id.eq_any(&photo_ids))
+                .set(albums::cover_photo_id.eq(None::<i32>))
+                .execute(conn)?;
+
+            Ok(find_album(conn, Owner::Any, album_id)?)
+        })
+    })
+    .await
+}
+
+/// Назначает обложкой альбома одну из его фотографий
+pub async fn set_album_cover(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+    photo_id: Option<i32>,
+) -> Result<Album, AlbumError> {
+    use crate::schema::{album_photos, albums};
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            find_album_for(conn, owner, album_id, AlbumRole::Editor)?;
+
+            if let Some(photo_id) = photo_id {
+                album_photos::table
+                    .find((album_id, photo_id))
+                    .select(album_photos::photo_id)
+                    .first::<i32>(conn)?;
+            }
+
+            Ok(diesel::update(albums::table.find(album_id))
+                .set(albums::cover_photo_id.eq(photo_id))
+                .returning(Album::as_returning())
+                .get_result(conn)?)
+        })
+    })
+    .await
+}
+
+/// Пользователи, с которыми поделились альбомом. Доступно только владельцу.
+pub async fn get_album_shares(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+) -> Result<Vec<AlbumShare>, DbError> {
+    use crate::schema::album_shares;
+
+    interact(pool, move |conn| {
+        find_album(conn, owner, album_id)?;
+
+        album_shares::table
+            .filter(album_shares::album_id.eq(album_id))
+            .select(AlbumShare::as_select())
+            .order(album_shares::created_at)
+            .load(conn)
+    })
+    .await
+}
+
+/// Выдаёт пользователю доступ к альбому или меняет его роль
+pub async fn share_album(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+    user_id: i32,
+    role: AlbumRole,
+) -> Result<AlbumShare, AlbumError> {
+    use crate::schema::{album_shares, users};
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            let album = find_album(conn, owner, album_id)?;
+            if album.user_id == user_id {
+                return Err(AlbumError::SelfShare);
+            }
+            users::table
+                .find(user_id)
+                .select(users::id)
+                .first::<i32>(conn)?;
+
+            Ok(diesel::insert_into(album_shares::table)
+                .values((
+                    album_shares::album_id.eq(album_id),
+                    album_shares::user_id.eq(user_id),
+                    album_shares::role.eq(role),
+                ))
+                .on_conflict((album_shares::album_id, album_shares::user_id))
+                .do_update()
+                .set(album_shares::role.eq(role))
+                .returning(AlbumShare::as_returning())
+                .get_result(conn)?)
+        })
+    })
+    .await
+}
+
+/// Отзывает доступ к альбому. Получатель может отказаться от доступа сам.
+pub async fn unshare_album(
+    pool: &DbPool,
+    owner: Owner,
+    album_id: i32,
+    user_id: i32,
+) -> Result<(), DbError> {
+    use crate::schema::album_shares;
+
+    interact(pool, move |conn| {
+        if owner.user_id() != Some(user_id) {
+            find_album(conn, owner, album_id)?;
+        }
+
+        match diesel::delete(album_shares::table.find((album_id, user_id))).execute(conn)? {
+            0 => Err(diesel::result::Error::NotFound),
+            _ => Ok(()),
+        }
+    })
+    .await
+}
+
+/// Альбом, к которому у `owner` есть доступ не ниже `role`. Владельцу и
+/// администратору доступно всё, без доступа альбом считается не найденным.
+pub fn find_album_for(
+    conn: &mut PgConnection,
+    owner: Owner,
+    album_id: i32,
+    role: AlbumRole,
+) -> Result<Album, AlbumError> {
+    use crate::schema::album_shares;
+
+    let album = find_album(conn, Owner::Any, album_id)?;
+    let Some(user_id) = owner.user_id() else {
+        return Ok(album);
+    };
+    if album.user_id == user_id {
+        return Ok(album);
+    }
+
+    let shared: Option<AlbumRole> = album_shares::table
+        .find((album_id, user_id))
+        .select(album_shares::role)
+        .first(conn)
+        .optional()?;
+    match shared {
+        Some(shared) if shared >= role => Ok(album),
+        Some(_) => Err(AlbumError::Forbidden),
+        None => Err(diesel::result::Error::NotFound.into()),
+    }
+}
+
+/// Альбом, принадлежащий `owner`
+pub fn find_album(
+    conn: &mut PgConnection,
+    owner: Owner,
+    album_id: i32,
+) -> Result<Album, diesel::result::Error> {
+    use crate::schema::albums;
+
+    let mut query = albums::table
+        .find(album_id)
         .select(Album::as_select())
-        .load(&mut connection())
-        .expect("Error loading albums")
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(albums::user_id.eq(user_id));
+    }
+
+    query.first(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::FakeMlBackend;
+    use crate::test_utils::{test_image, TestEnv};
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn contributor_adds_only_own_photos() {
+        let env = TestEnv::new();
+        let ml = FakeMlBackend::new();
+        let album_owner = env.create_user().await.id;
+        let contributor = env.create_user().await.id;
+
+        let album = create_album(
+            &env.pool,
+            &ml,
+            NewAlbum {
+                title: "Поездка".to_string(),
+                rule: None,
+            },
+            album_owner,
+        )
+        .await
+        .unwrap();
+        share_album(
+            &env.pool,
+            Owner::User(album_owner),
+            album.id,
+            contributor,
+            AlbumRole::Contributor,
+        )
+        .await
+        .unwrap();
+
+        let owner_photo = env.upload(&ml, album_owner, test_image([200, 0, 0])).await;
+        let contributor_photo = env.upload(&ml, contributor, test_image([0, 200, 0])).await;
+
+        let result = add_photos_to_album(
+            &env.pool,
+            Owner::User(contributor),
+            album.id,
+            vec![owner_photo],
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(AlbumError::Diesel(diesel::result::Error::NotFound))
+        ));
+
+        let album = add_photos_to_album(
+            &env.pool,
+            Owner::User(contributor),
+            album.id,
+            vec![contributor_photo],
+        )
+        .await
+        .unwrap();
+        assert_eq!(album.photo_count, 1);
+
+        let album = add_photos_to_album(
+            &env.pool,
+            Owner::User(album_owner),
+            album.id,
+            vec![owner_photo],
+        )
+        .await
+        .unwrap();
+        assert_eq!(album.photo_count, 2);
+    }
 }
@@ -0,0 +1,125 @@
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+use crate::{
+    db_connection::{interact, DbPool},
+    errors::{ApiKeyError, DbError},
+    models::{ApiKey, ApiKeyScope, CreatedApiKey, NewApiKey, User},
+    services::auth::{random_token, token_hash},
+};
+
+/// Начало ключа, по которому его отличают от токена доступа
+pub const API_KEY_PREFIX: &str = "rk_";
+
+/// Сколько символов ключа хранится открыто для показа в списке
+const DISPLAY_PREFIX_LEN: usize = 10;
+
+/// Наибольшая длина названия ключа, как в колонке `api_keys.name`
+const MAX_NAME_LEN: usize = 100;
+
+/// Создаёт персональный ключ пользователя. Сам ключ возвращается только здесь,
+/// в базе остаётся его хэш.
+pub async fn create_api_key(
+    pool: &DbPool,
+    user_id: i32,
+    new_key: NewApiKey,
+) -> Result<CreatedApiKey, ApiKeyError> {
+    use crate::schema::api_keys;
+
+    let name = new_key.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(ApiKeyError::InvalidName);
+    }
+    if new_key
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(ApiKeyError::InvalidExpiry);
+    }
+
+    let key = format!("{API_KEY_PREFIX}{}", random_token());
+    let (prefix, hash) = (key[..DISPLAY_PREFIX_LEN].to_string(), token_hash(&key));
+    let api_key = interact(pool, move |conn| {
+        diesel::insert_into(api_keys::table)
+            .values((
+                api_keys::user_id.eq(user_id),
+                api_keys::name.eq(name),
+                api_keys::prefix.eq(prefix),
+                api_keys::key_hash.eq(hash),
+                api_keys::scope.eq(new_key.scope),
+                api_keys::expires_at.eq(new_key.expires_at),
+            ))
+            .returning(ApiKey::as_returning())
+            .get_result(conn)
+    })
+    .await?;
+
+    Ok(CreatedApiKey { api_key, key })
+}
+
+pub async fn get_api_keys(pool: &DbPool, user_id: i32) -> Result<Vec<ApiKey>, DbError> {
+    use crate::schema::api_keys;
+
+    interact(pool, move |conn| {
+        api_keys::table
+            .filter(api_keys::user_id.eq(user_id))
+            .select(ApiKey::as_select())
+            .order(api_keys::id.desc())
+            .load(conn)
+    })
+    .await
+}
+
+/// Отзывает ключ пользователя
+pub async fn delete_api_key(pool: &DbPool, user_id: i32, key_id: i32) -> Result<(), DbError> {
+    use crate::schema::api_keys;
+
+    interact(pool, move |conn| {
+        let deleted = diesel::delete(api_keys::table.find(key_id))
+            .filter(api_keys::user_id.eq(user_id))
+            .execute(conn)?;
+        match deleted {
+            0 => Err(diesel::result::Error::NotFound),
+            _ => Ok(()),
+        }
+    })
+    .await
+}
+
+/// Владелец действующего ключа, его id и права. Каждое предъявление ключа
+/// обновляет время последнего использования.
+pub async fn authenticate_api_key(
+    pool: &DbPool,
+    key: &str,
+) -> Result<Option<(User, i32, ApiKeyScope)>, DbError> {
+    use crate::schema::{api_keys, users};
+
+    let hash = token_hash(key);
+    interact(pool, move |conn| {
+        let now = Utc::now();
+        let api_key: Option<(i32, i32, ApiKeyScope)> = diesel::update(api_keys::table)
+            .filter(api_keys::key_hash.eq(hash))
+            .filter(
+                api_keys::expires_at
+                    .is_null()
+                    .or(api_keys::expires_at.gt(now)),
+            )
+            .set(api_keys::last_used_at.eq(now))
+            .returning((api_keys::id, api_keys::user_id, api_keys::scope))
+            .get_result(conn)
+            .optional()?;
+        let Some((key_id, user_id, scope)) = api_key else {
+            return Ok(None);
+        };
+
+        let user = users::table
+            .find(user_id)
+            .select(User::as_select())
+            .first(conn)?;
+        Ok(Some((user, key_id, scope)))
+    })
+    .await
+}
@@ -0,0 +1,322 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    dsl::exists, Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    QueryResult, RunQueryDsl, SelectableHelper,
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::config::AuthConfig;
+use crate::db_connection::{interact, interact_with, DbPool};
+use crate::errors::{AuthError, DbError};
+use crate::middleware::authorize::{encode_jwt, verify_password};
+use crate::models::User;
+use crate::services::users::get_user_by_email;
+
+/// Случайных байт в токене обновления и идентификаторе сессии
+const TOKEN_BYTES: usize = 32;
+
+/// Токены, которые выдаются при входе и каждом обновлении
+pub struct SessionTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+pub fn random_token() -> String {
+    let mut token = [0u8; TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut token);
+    URL_SAFE_NO_PAD.encode(token)
+}
+
+/// SHA-256 токена, в базе хранится только он
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Проверяет почту и пароль и начинает новую сессию
+pub async fn sign_in(
+    pool: &DbPool,
+    config: &AuthConfig,
+    email: &str,
+    password: String,
+) -> Result<SessionTokens, AuthError> {
+    let user = get_user_by_email(pool, email)
+        .await?
+        .ok_or(AuthError::InvalidCredentials)?;
+
+    // bcrypt намеренно медленный, поэтому проверка выполняется вне async потоков
+    let password_hash = user.password.clone();
+    let is_valid =
+        tokio::task::spawn_blocking(move || verify_password(&password, &password_hash)).await??;
+    if !is_valid {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    start_session(pool, config, user).await
+}
+
+/// Начинает новую сессию пользователя, уже подтвердившего свою личность
+pub async fn start_session(
+    pool: &DbPool,
+    config: &AuthConfig,
+    user: User,
+) -> Result<SessionTokens, AuthError> {
+    let session_id = random_token();
+    let refresh_token = random_token();
+    let expires_at = refresh_expiry(config);
+    let (user_id, hash, sid) = (user.id, token_hash(&refresh_token), session_id.clone());
+    interact(pool, move |conn| {
+        delete_expired_tokens(conn, user_id)?;
+        insert_refresh_token(conn, &sid, user_id, hash, expires_at)
+    })
+    .await?;
+
+    Ok(SessionTokens {
+        access_token: encode_jwt(user.email, session_id, config)?,
+        refresh_token,
+    })
+}
+
+/// Меняет токен обновления на новый и выдаёт новый токен доступа той же
+/// сессии. Повторное предъявление уже заменённого токена означает, что его
+/// украли, и тогда отзывается вся сессия.
+pub async fn refresh_session(
+    pool: &DbPool,
+    config: &AuthConfig,
+    refresh_token: &str,
+) -> Result<SessionTokens, AuthError> {
+    use crate::schema::{refresh_tokens, users};
+
+    let new_refresh_token = random_token();
+    let (hash, new_hash) = (token_hash(refresh_token), token_hash(&new_refresh_token));
+    let expires_at = refresh_expiry(config);
+
+    let rotated = interact_with(pool, move |conn| {
+        let (token_id, session_id, user_id, token_expires_at, revoked_at): (
+            i32,
+            String,
+            i32,
+            DateTime<Utc>,
+            Option<DateTime<Utc>>,
+        ) = refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(hash))
+            .select((
+                refresh_tokens::id,
+                refresh_tokens::session_id,
+                refresh_tokens::user_id,
+                refresh_tokens::expires_at,
+                refresh_tokens::revoked_at,
+            ))
+            .first(conn)
+            .optional()?
+            .ok_or(AuthError::InvalidRefreshToken)?;
+        if token_expires_at <= Utc::now() {
+            return Err(AuthError::InvalidRefreshToken);
+        }
+
+        let rotated = match revoked_at {
+            Some(_) => false,
+            None => conn.transaction(|conn| {
+                // Условие на revoked_at не даёт двум параллельным запросам
+                // обменять один токен дважды
+                let revoked = diesel::update(refresh_tokens::table.find(token_id))
+                    .filter(refresh_tokens::revoked_at.is_null())
+                    .set(refresh_tokens::revoked_at.eq(Utc::now()))
+                    .execute(conn)?;
+                if revoked == 0 {
+                    return Ok::<_, diesel::result::Error>(false);
+                }
+
+                delete_expired_tokens(conn, user_id)?;
+                insert_refresh_token(conn, &session_id, user_id, new_hash, expires_at)?;
+                Ok(true)
+            })?,
+        };
+        if !rotated {
+            revoke_session_tokens(conn, &session_id)?;
+            return Ok(None);
+        }
+
+        let email: String = users::table
+            .find(user_id)
+            .select(users::email)
+            .first(conn)?;
+        Ok(Some((email, session_id)))
+    })
+    .await?;
+
+    let (email, session_id) = rotated.ok_or(AuthError::RefreshTokenReused)?;
+    Ok(SessionTokens {
+        access_token: encode_jwt(email, session_id, config)?,
+        refresh_token: new_refresh_token,
+    })
+}
+
+/// Сессия, к которой относится токен обновления, в том числе уже заменённый
+pub async fn get_refresh_token_session(
+    pool: &DbPool,
+    refresh_token: &str,
+) -> Result<Option<String>, DbError> {
+    use crate::schema::refresh_tokens;
+
+    let hash = token_hash(refresh_token);
+    interact(pool, move |conn| {
+        refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(hash))
+            .select(refresh_tokens::session_id)
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+/// Отзывает сессию `session_id`, а при `all` - все сессии её пользователя
+pub async fn revoke_session(pool: &DbPool, session_id: String, all: bool) -> Result<(), DbError> {
+    use crate::schema::refresh_tokens;
+
+    interact(pool, move |conn| {
+        if !all {
+            return revoke_session_tokens(conn, &session_id);
+        }
+
+        let user_id: Option<i32> = refresh_tokens::table
+            .filter(refresh_tokens::session_id.eq(&session_id))
+            .select(refresh_tokens::user_id)
+            .first(conn)
+            .optional()?;
+        match user_id {
+            Some(user_id) => revoke_user_sessions(conn, user_id, None),
+            None => Ok(()),
+        }
+    })
+    .await
+}
+
+/// Отзывает все сессии пользователя, кроме `except`
+pub fn revoke_user_sessions(
+    conn: &mut PgConnection,
+    user_id: i32,
+    except: Option<&str>,
+) -> QueryResult<()> {
+    use crate::schema::refresh_tokens;
+
+    let mut query = diesel::update(refresh_tokens::table)
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .filter(refresh_tokens::revoked_at.is_null())
+        .into_boxed();
+    if let Some(session_id) = except {
+        query = query.filter(refresh_tokens::session_id.ne(session_id));
+    }
+    query
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Пользователь токена доступа, если его сессия не отозвана и не истекла
+pub async fn get_session_user(
+    pool: &DbPool,
+    email: String,
+    session_id: String,
+) -> Result<Option<User>, DbError> {
+    use crate::schema::{refresh_tokens, users};
+
+    interact(pool, move |conn| {
+        users::table
+            .filter(users::email.eq(email))
+            .filter(exists(
+                refresh_tokens::table
+                    .filter(refresh_tokens::user_id.eq(users::id))
+                    .filter(refresh_tokens::session_id.eq(session_id))
+                    .filter(refresh_tokens::revoked_at.is_null())
+                    .filter(refresh_tokens::expires_at.gt(Utc::now())),
+            ))
+            .select(User::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+fn refresh_expiry(config: &AuthConfig) -> DateTime<Utc> {
+    Utc::now() + Duration::seconds(config.refresh_token_lifetime_secs)
+}
+
+fn insert_refresh_token(
+    conn: &mut PgConnection,
+    session_id: &str,
+    user_id: i32,
+    token_hash: String,
+    expires_at: DateTime<Utc>,
+) -> QueryResult<()> {
+    use crate::schema::refresh_tokens;
+
+    diesel::insert_into(refresh_tokens::table)
+        .values((
+            refresh_tokens::session_id.eq(session_id),
+            refresh_tokens::user_id.eq(user_id),
+            refresh_tokens::token_hash.eq(token_hash),
+            refresh_tokens::expires_at.eq(expires_at),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn revoke_session_tokens(conn: &mut PgConnection, session_id: &str) -> QueryResult<()> {
+    use crate::schema::refresh_tokens;
+
+    diesel::update(refresh_tokens::table)
+        .filter(refresh_tokens::session_id.eq(session_id))
+        .filter(refresh_tokens::revoked_at.is_null())
+        .set(refresh_tokens::revoked_at.eq(Utc::now()))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Истёкшие токены больше не нужны даже для поиска повторного использования
+fn delete_expired_tokens(conn: &mut PgConnection, user_id: i32) -> QueryResult<()> {
+    use crate::schema::refresh_tokens;
+
+    diesel::delete(refresh_tokens::table)
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .filter(refresh_tokens::expires_at.le(Utc::now()))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestEnv;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reused_refresh_token_revokes_session() {
+        let env = TestEnv::new();
+        let mut config = env.config.auth.clone();
+        config.jwt_secret = "jwt-test-secret".to_string();
+        let user = env.create_user().await;
+
+        let first = start_session(&env.pool, &config, user).await.unwrap();
+        let second = refresh_session(&env.pool, &config, &first.refresh_token)
+            .await
+            .unwrap();
+        assert_ne!(first.refresh_token, second.refresh_token);
+
+        // Повторный обмен уже заменённого токена выглядит как кража
+        assert!(matches!(
+            refresh_session(&env.pool, &config, &first.refresh_token).await,
+            Err(AuthError::RefreshTokenReused)
+        ));
+        // и отзывает всю сессию, включая выданный взамен токен
+        assert!(matches!(
+            refresh_session(&env.pool, &config, &second.refresh_token).await,
+            Err(AuthError::RefreshTokenReused)
+        ));
+        assert!(matches!(
+            refresh_session(&env.pool, &config, "unknown").await,
+            Err(AuthError::InvalidRefreshToken)
+        ));
+    }
+}
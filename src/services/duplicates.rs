@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use diesel::{
+    sql_types::{BigInt, Double, Integer, Nullable},
+    ExpressionMethods, QueryDsl, QueryableByName, RunQueryDsl, SelectableHelper,
+};
+
+use crate::config::DuplicatesConfig;
+use crate::db_connection::{interact, DbPool};
+use crate::errors::DbError;
+use crate::models::{DuplicateGroup, ListPhoto, Owner, ResolveDuplicates};
+use crate::services::photos::{delete_photo_by_id, get_photo_by_id};
+use crate::storage::Storage;
+
+/// Сколько пар похожих фотографий рассматривается за один запрос
+const MAX_DUPLICATE_PAIRS: i64 = 1000;
+
+/// Сколько ближайших по эмбеддингу фотографий проверяется для каждой
+const DUPLICATE_CANDIDATES: i64 = 10;
+
+/// Для каждой фотографии ближайшие по CLIP эмбеддингу фотографии того же
+/// пользователя берутся из HNSW индекса `photos_embedding_idx`, и только эти
+/// пары сравниваются по расстоянию эмбеддингов и перцептивных хэшей
+const DUPLICATE_PAIRS_QUERY: &str = "
+    SELECT DISTINCT
+        LEAST(photos.id, candidate.id) AS left_id,
+        GREATEST(photos.id, candidate.id) AS right_id
+    FROM photos
+    CROSS JOIN LATERAL (
+        SELECT other.id, other.perceptual_hash,
+            other.embedding <=> photos.embedding AS distance
+        FROM photos other
+        WHERE other.user_id = photos.user_id
+            AND other.id <> photos.id
+            AND other.embedding IS NOT NULL
+        ORDER BY other.embedding <=> photos.embedding
+        LIMIT $1
+    ) candidate
+    WHERE photos.embedding IS NOT NULL
+        AND ($2 IS NULL OR photos.user_id = $2)
+        AND (
+            candidate.distance <= $3
+            OR length(replace(((photos.perceptual_hash # candidate.perceptual_hash)::bit(64))::text, '0', '')) <= $4
+        )
+    ORDER BY left_id, right_id
+    LIMIT $5";
+
+#[derive(QueryableByName)]
+struct DuplicatePair {
+    #[diesel(sql_type = Integer)]
+    left_id: i32,
+    #[diesel(sql_type = Integer)]
+    right_id: i32,
+}
+
+/// Группы почти одинаковых фотографий: с близким перцептивным хэшем или CLIP
+/// эмбеддингом. Фотографии сравниваются только с ближайшими по эмбеддингу
+/// фотографиями того же пользователя.
+pub async fn get_duplicate_groups(
+    pool: &DbPool,
+    owner: Owner,
+    config: &DuplicatesConfig,
+) -> Result<Vec<DuplicateGroup>, DbError> {
+    use crate::schema::photos;
+
+    let query = diesel::sql_query(DUPLICATE_PAIRS_QUERY)
+        .bind::<BigInt, _>(DUPLICATE_CANDIDATES)
+        .bind::<Nullable<Integer>, _>(owner.user_id())
+        .bind::<Double, _>(config.embedding_distance)
+        .bind::<Integer, _>(config.hash_distance as i32)
+        .bind::<BigInt, _>(MAX_DUPLICATE_PAIRS);
+
+    interact(pool, move |conn| {
+        let pairs: Vec<(i32, i32)> = query
+            .load(conn)?
+            .into_iter()
+            .map(|pair: DuplicatePair| (pair.left_id, pair.right_id))
+            .collect();
+        let groups = group_pairs(&pairs);
+
+        let ids: Vec<i32> = groups.iter().flatten().copied().collect();
+        let photos_by_id: HashMap<i32, ListPhoto> = photos::table
+            .filter(photos::id.eq_any(ids))
+            .select(ListPhoto::as_select())
+            .load(conn)?
+            .into_iter()
+            .map(|photo: ListPhoto| (photo.id, photo))
+            .collect();
+
+        Ok(groups
+            .into_iter()
+            .map(|group| DuplicateGroup {
+                photos: group
+                    .iter()
+                    .filter_map(|id| photos_by_id.get(id).cloned())
+                    .collect(),
+            })
+            .collect())
+    })
+    .await
+}
+
+/// Оставляет одну фотографию из группы и удаляет остальные
+pub async fn resolve_duplicates(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    owner: Owner,
+    resolve: ResolveDuplicates,
+) -> Result<ListPhoto, DbError> {
+    let kept = get_photo_by_id(pool, owner, resolve.keep).await?;
+
+    let mut delete = resolve.delete;
+    delete.retain(|photo_id| *photo_id != kept.id);
+    delete.sort_unstable();
+    delete.dedup();
+
+    // Сначала проверяются все фотографии, чтобы не удалить часть из них
+    for &photo_id in &delete {
+        get_photo_by_id(pool, owner, photo_id).await?;
+    }
+    for photo_id in delete {
+        delete_photo_by_id(pool, storage, owner, photo_id).await?;
+    }
+
+    Ok(kept)
+}
+
+/// Объединяет пары похожих фотографий в группы связности, фотографии в
+/// группе и сами группы упорядочены по id
+fn group_pairs(pairs: &[(i32, i32)]) -> Vec<Vec<i32>> {
+    let mut parent: HashMap<i32, i32> = HashMap::new();
+
+    fn root(parent: &mut HashMap<i32, i32>, id: i32) -> i32 {
+        let next = *parent.entry(id).or_insert(id);
+        if next == id {
+            return id;
+        }
+        let found = root(parent, next);
+        parent.insert(id, found);
+        found
+    }
+
+    for &(left, right) in pairs {
+        let left = root(&mut parent, left);
+        let right = root(&mut parent, right);
+        if left != right {
+            parent.insert(left.max(right), left.min(right));
+        }
+    }
+
+    let mut groups: HashMap<i32, Vec<i32>> = HashMap::new();
+    let ids: Vec<i32> = parent.keys().copied().collect();
+    for id in ids {
+        let group_root = root(&mut parent, id);
+        groups.entry(group_root).or_default().push(id);
+    }
+
+    let mut groups: Vec<Vec<i32>> = groups.into_values().collect();
+    for group in &mut groups {
+        group.sort_unstable();
+    }
+    groups.sort_unstable_by_key(|group| group[0]);
+    groups
+}
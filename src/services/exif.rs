@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::{Exif, In, Tag, Value};
+
+use crate::models::PhotoExif;
+
+/// Длина строковых колонок EXIF в таблице `photos`
+const MAX_TEXT_LEN: usize = 100;
+
+/// Извлекает метаданные EXIF из исходного файла. Файлы без EXIF или с
+/// повреждённым EXIF дают пустые метаданные.
+pub fn read_exif(file_content: &[u8]) -> PhotoExif {
+    let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(file_content)) {
+        Ok(exif) => exif,
+        Err(err) => {
+            log::debug!("No EXIF read: {err}");
+            return PhotoExif::default();
+        }
+    };
+
+    PhotoExif {
+        taken_at: date_time(&exif, Tag::DateTimeOriginal)
+            .or_else(|| date_time(&exif, Tag::DateTimeDigitized))
+            .or_else(|| date_time(&exif, Tag::DateTime)),
+        camera_make: text(&exif, Tag::Make),
+        camera_model: text(&exif, Tag::Model),
+        lens_model: text(&exif, Tag::LensModel),
+        exposure_time: rational(&exif, Tag::ExposureTime, 0),
+        f_number: rational(&exif, Tag::FNumber, 0),
+        iso: uint(&exif, Tag::PhotographicSensitivity).and_then(|iso| i32::try_from(iso).ok()),
+        focal_length: rational(&exif, Tag::FocalLength, 0),
+        orientation: uint(&exif, Tag::Orientation)
+            .filter(|orientation| (1..=8).contains(orientation))
+            .map(|orientation| orientation as i16),
+        latitude: coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S', 90.0),
+        longitude: coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W', 180.0),
+    }
+}
+
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    let Value::Ascii(ref values) = exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let value = String::from_utf8_lossy(values.first()?);
+    let value = value.trim_matches(char::from(0)).trim();
+
+    (!value.is_empty()).then(|| value.chars().take(MAX_TEXT_LEN).collect())
+}
+
+fn uint(exif: &Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+fn rational(exif: &Exif, tag: Tag, index: usize) -> Option<f64> {
+    let Value::Rational(ref values) = exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let value = values.get(index)?;
+    (value.denom != 0).then(|| value.to_f64())
+}
+
+fn date_time(exif: &Exif, tag: Tag) -> Option<NaiveDateTime> {
+    let Value::Ascii(ref values) = exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let date_time = exif::DateTime::from_ascii(values.first()?).ok()?;
+
+    NaiveDate::from_ymd_opt(
+        date_time.year.into(),
+        date_time.month.into(),
+        date_time.day.into(),
+    )?
+    .and_hms_opt(
+        date_time.hour.into(),
+        date_time.minute.into(),
+        date_time.second.into(),
+    )
+}
+
+/// Координата в градусах из значения градусы/минуты/секунды и полушария
+fn coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: u8, max: f64) -> Option<f64> {
+    let degrees = rational(exif, tag, 0)?;
+    let minutes = rational(exif, tag, 1).unwrap_or_default();
+    let seconds = rational(exif, tag, 2).unwrap_or_default();
+    let value = degrees + minutes / 60.0 + seconds / 3600.0;
+
+    let is_negative = match exif
+        .get_field(ref_tag, In::PRIMARY)
+        .map(|field| &field.value)
+    {
+        Some(Value::Ascii(values)) => values
+            .first()
+            .and_then(|value| value.first())
+            .is_some_and(|hemisphere| hemisphere.eq_ignore_ascii_case(&negative_ref)),
+        _ => false,
+    };
+    let value = if is_negative { -value } else { value };
+
+    (value.abs() <= max).then_some(value)
+}
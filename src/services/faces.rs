@@ -0,0 +1,247 @@
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+use crate::db_connection::{interact, DbPool};
+use crate::errors::DbError;
+use crate::models::{
+    FaceRejection, FaceSort, FacesQuery, ListFace, NewPerson, Owner, Page, Person,
+};
+use crate::services::pagination::PageQuery;
+
+pub async fn get_faces(
+    pool: &DbPool,
+    owner: Owner,
+    params: FacesQuery,
+    page: PageQuery<FaceSort>,
+) -> Result<Page<ListFace>, DbError> {
+    use crate::schema::{faces, photos};
+
+    let filtered = move || {
+        let mut query = faces::table.into_boxed();
+        if let Some(user_id) = owner.user_id() {
+            query = query.filter(
+                faces::photo_id.eq_any(
+                    photos::table
+                        .select(photos::id)
+                        .filter(photos::user_id.eq(user_id)),
+                ),
+            );
+        }
+        if let Some(person_id) = params.person_id {
+            query = query.filter(faces::person_id.eq(person_id));
+        }
+        if let Some(photo_id) = params.photo_id {
+            query = query.filter(faces::photo_id.eq(photo_id));
+        }
+        if let Some(is_ignored) = params.is_ignored {
+            query = query.filter(faces::is_ignored.eq(is_ignored));
+        }
+        query
+    };
+
+    interact(pool, move |conn| {
+        let total = filtered().count().get_result(conn)?;
+
+        let mut query = filtered()
+            .select((ListFace::as_select(), page.key()))
+            .order(page.order())
+            .offset(page.offset())
+            .limit(page.fetch_limit());
+        if let Some(after) = page.after() {
+            query = query.filter(after);
+        }
+
+        Ok(page.finish(query.load(conn)?, total, |face| face.id))
+    })
+    .await
+}
+
+pub async fn get_face_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    face_id: i32,
+) -> Result<ListFace, DbError> {
+    interact(pool, move |conn| {
+        let (face, _) = get_face_with_owner(conn, owner, face_id, false)?;
+        Ok(face)
+    })
+    .await
+}
+
+/// Переносит лицо в другую личность того же пользователя вручную
+pub async fn move_face_to_person(
+    pool: &DbPool,
+    owner: Owner,
+    face_id: i32,
+    person_id: i32,
+) -> Result<ListFace, DbError> {
+    use crate::schema::{face_rejections, faces, persons};
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let (face, user_id) = get_face_with_owner(conn, owner, face_id, true)?;
+            persons::table
+                .find(person_id)
+                .filter(persons::user_id.eq(user_id))
+                .select(persons::id)
+                .first::<i32>(conn)?;
+
+            if face.person_id != Some(person_id) {
+                release_face(conn, &face)?;
+            }
+
+            diesel::delete(
+                face_rejections::table
+                    .filter(face_rejections::face_id.eq(face_id))
+                    .filter(face_rejections::person_id.eq(person_id)),
+            )
+            .execute(conn)?;
+
+            diesel::update(faces::table.find(face_id))
+                .set((
+                    faces::person_id.eq(person_id),
+                    faces::is_manual.eq(true),
+                    faces::is_ignored.eq(false),
+                ))
+                .returning(ListFace::as_returning())
+                .get_result(conn)
+        })
+    })
+    .await
+}
+
+/// Выделяет лицо в новую личность
+pub async fn detach_face(pool: &DbPool, owner: Owner, face_id: i32) -> Result<Person, DbError> {
+    use crate::schema::{faces, persons};
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let (face, user_id) = get_face_with_owner(conn, owner, face_id, true)?;
+            release_face(conn, &face)?;
+
+            let person: Person = diesel::insert_into(persons::table)
+                .values(&NewPerson {
+                    title: "Unknown".to_string(),
+                    avatar: face.path.clone().unwrap_or_default(),
+                    user_id,
+                })
+                .returning(Person::as_returning())
+                .get_result(conn)?;
+
+            diesel::update(faces::table.find(face_id))
+                .set((
+                    faces::person_id.eq(person.id),
+                    faces::is_manual.eq(true),
+                    faces::is_ignored.eq(false),
+                ))
+                .execute(conn)?;
+
+            Ok(person)
+        })
+    })
+    .await
+}
+
+/// Помечает лицо как "не лицо" или снимает эту пометку
+pub async fn set_face_ignored(
+    pool: &DbPool,
+    owner: Owner,
+    face_id: i32,
+    ignored: bool,
+) -> Result<ListFace, DbError> {
+    use crate::schema::faces;
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let (face, _) = get_face_with_owner(conn, owner, face_id, true)?;
+
+            if ignored {
+                release_face(conn, &face)?;
+                diesel::update(faces::table.find(face_id))
+                    .set((
+                        faces::person_id.eq(None::<i32>),
+                        faces::is_manual.eq(true),
+                        faces::is_ignored.eq(true),
+                    ))
+                    .returning(ListFace::as_returning())
+                    .get_result(conn)
+            } else {
+                diesel::update(faces::table.find(face_id))
+                    .set(faces::is_ignored.eq(false))
+                    .returning(ListFace::as_returning())
+                    .get_result(conn)
+            }
+        })
+    })
+    .await
+}
+
+/// Лицо, доступное `owner`, и id владельца фотографии, на которой оно найдено
+fn get_face_with_owner(
+    conn: &mut PgConnection,
+    owner: Owner,
+    face_id: i32,
+    for_update: bool,
+) -> Result<(ListFace, i32), diesel::result::Error> {
+    use crate::schema::{faces, photos};
+
+    let query = faces::table
+        .inner_join(photos::table)
+        .filter(faces::id.eq(face_id))
+        .select((ListFace::as_select(), photos::user_id));
+
+    let (face, user_id): (ListFace, i32) = if for_update {
+        query.for_update().first(conn)?
+    } else {
+        query.first(conn)?
+    };
+
+    match owner.user_id() {
+        Some(owner_id) if owner_id != user_id => Err(diesel::result::Error::NotFound),
+        _ => Ok((face, user_id)),
+    }
+}
+
+/// Запоминает, что лицо убрано из текущей личности, и меняет аватар личности,
+/// если им было это лицо
+fn release_face(conn: &mut PgConnection, face: &ListFace) -> Result<(), diesel::result::Error> {
+    use crate::schema::{face_rejections, faces, persons};
+
+    let Some(person_id) = face.person_id else {
+        return Ok(());
+    };
+
+    diesel::insert_into(face_rejections::table)
+        .values(&FaceRejection {
+            face_id: face.id,
+            person_id,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    let avatar: String = persons::table
+        .find(person_id)
+        .select(persons::avatar)
+        .first(conn)?;
+
+    if face.path.as_ref() == Some(&avatar) {
+        let new_avatar: Option<Option<String>> = faces::table
+            .filter(faces::person_id.eq(person_id))
+            .filter(faces::id.ne(face.id))
+            .filter(faces::path.is_not_null())
+            .select(faces::path)
+            .order(faces::id)
+            .first(conn)
+            .optional()?;
+
+        if let Some(Some(new_avatar)) = new_avatar {
+            diesel::update(persons::table.find(person_id))
+                .set(persons::avatar.eq(new_avatar))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
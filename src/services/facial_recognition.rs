@@ -1,169 +1,567 @@
-use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
-use image::{io::Reader as ImageReader, DynamicImage, RgbImage};
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, QueryResult, RunQueryDsl, SelectableHelper,
+};
+use futures::future::try_join_all;
+use image::{io::Reader as ImageReader, RgbImage};
 use pgvector::{Vector, VectorExpressionMethods};
-use reqwest::multipart;
-use serde::{Deserialize, Serialize};
-
-use crate::db_connection::connection;
-use crate::errors::CreatePhotoError;
-use crate::models::{Face, NewFace, NewPerson, NewPhoto, Photo, PhotoForm};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::db_connection::{interact, interact_with, DbPool};
+use crate::errors::{AlbumError, CreatePhotoError, StorageError};
+use crate::ml::{MlBackend, RecognizedFaceOutput};
+use crate::models::{
+    AlbumRole, Face, JobKind, ListFace, NewFace, NewJob, NewPerson, NewPhoto, Owner, Photo,
+    PhotoForm, UploadedPhoto,
+};
+use crate::services::albums::{find_album_for, refresh_smart_albums_for_photo};
+use crate::services::exif::read_exif;
+use crate::services::images::{
+    apply_orientation, encode_jpeg, encode_renditions, flatten_to_rgb, perceptual_hash, EncodedFile,
+};
+use crate::services::jobs::enqueue_job;
+use crate::services::photos::delete_files;
+use crate::storage::Storage;
 
 use std::io::Cursor;
+use std::sync::Arc;
+
+const FACE_MATCH_CANDIDATES: i64 = 20;
+
+/// Качество JPEG для показа и вырезанных лиц, как у `image` по умолчанию
+const JPEG_QUALITY: u8 = 75;
+
+/// Уникальный индекс содержимого файлов пользователя
+const CONTENT_HASH_INDEX: &str = "photos_user_content_hash_idx";
+
+/// Сохраняет загруженный файл и ставит задачу на его обработку. Повторная
+/// загрузка того же файла тем же пользователем отклоняется.
+///
+/// Исходный файл записывается в хранилище между двумя транзакциями, чтобы не
+/// держать соединение с базой на время загрузки. Если записать его не
+/// удалось, строка изображения удаляется.
+pub async fn create_photo(
+    pool: &DbPool,
+    storage: Arc<dyn Storage>,
+    config: &Config,
+    photo_form: PhotoForm,
+    uid: i32,
+) -> Result<UploadedPhoto, CreatePhotoError> {
+    use crate::schema::photos;
+
+    let file_content = photo_form.photo_image.contents.to_vec();
+
+    // Формат проверяется без декодирования, само декодирование - в задаче
+    let format = image::guess_format(&file_content)?;
+    let extension = format.extensions_str().first().copied().unwrap_or("bin");
+    let mime_type = format.to_mime_type();
+
+    let content_hash = format!("{:x}", Sha256::digest(&file_content));
+    let photo_id = interact_with(pool, move |conn| {
+        insert_photo(conn, &photo_form, uid, content_hash)
+    })
+    .await?;
+
+    // Исходный файл хранится без изменений, для показа и ML из него в задаче
+    // делается отдельный JPEG
+    let original_path = format!("{}/{photo_id}.{extension}", config.storage.originals_dir);
+    let uploaded = match storage.put(&original_path, file_content, mime_type).await {
+        Ok(()) => {
+            let original_path = original_path.clone();
+            interact_with(pool, move |conn| {
+                conn.transaction(|conn| {
+                    diesel::update(photos::table.find(photo_id))
+                        .set((
+                            photos::original_path.eq(&original_path),
+                            photos::mime_type.eq(mime_type),
+                        ))
+                        .execute(conn)?;
+
+                    let job = enqueue_job(
+                        conn,
+                        NewJob {
+                            kind: JobKind::ProcessPhoto,
+                            photo_id: Some(photo_id),
+                        },
+                    )?;
+
+                    Ok(UploadedPhoto {
+                        photo_id,
+                        job_id: job.id,
+                    })
+                })
+            })
+            .await
+        }
+        Err(err) => Err(err.into()),
+    };
+
+    if uploaded.is_err() {
+        delete_files(storage.as_ref(), [original_path]).await;
+        let deleted = interact(pool, move |conn| {
+            diesel::delete(photos::table.find(photo_id)).execute(conn)
+        })
+        .await;
+        if let Err(err) = deleted {
+            log::warn!("Error removing photo {photo_id} after failed upload: {err}");
+        }
+    }
 
-const UPLOAD_DIR_IMAGES: &str = "storage/images";
-const UPLOAD_DIR_FACES: &str = "storage/faces";
+    uploaded
+}
 
-pub async fn create_photo(photo_form: PhotoForm, uid: i32) -> Result<(), CreatePhotoError> {
+/// Добавляет строку загруженного изображения, пока без исходного файла
+fn insert_photo(
+    conn: &mut PgConnection,
+    photo_form: &PhotoForm,
+    uid: i32,
+    content_hash: String,
+) -> Result<i32, CreatePhotoError> {
+    use crate::schema::{album_photos, photos};
+
+    let result = conn.transaction(|conn| {
+        if let Some(album_id) = photo_form.album_id {
+            let album = find_album_for(conn, Owner::User(uid), album_id, AlbumRole::Contributor)?;
+            if album.rule.is_some() {
+                return Err(AlbumError::SmartAlbum.into());
+            }
+        }
+
+        if let Some(photo_id) = find_duplicate(conn, uid, &content_hash)? {
+            return Err(CreatePhotoError::Duplicate(photo_id));
+        }
+
+        let photo_id: i32 = diesel::insert_into(photos::table)
+            .values(NewPhoto::from_form(photo_form, uid, content_hash.clone()))
+            .returning(photos::id)
+            .get_result(conn)?;
+
+        if let Some(album_id) = photo_form.album_id {
+            diesel::insert_into(album_photos::table)
+                .values((
+                    album_photos::album_id.eq(album_id),
+                    album_photos::photo_id.eq(photo_id),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(photo_id)
+    });
+
+    // Параллельная загрузка того же файла проходит проверку выше
+    // одновременно с этой и успевает вставить строку первой
+    match result {
+        Err(CreatePhotoError::DieselError(DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            info,
+        ))) if info.constraint_name() == Some(CONTENT_HASH_INDEX) => {
+            match find_duplicate(conn, uid, &content_hash)? {
+                Some(photo_id) => Err(CreatePhotoError::Duplicate(photo_id)),
+                None => Err(diesel::result::Error::NotFound.into()),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Фотография пользователя с тем же содержимым файла
+fn find_duplicate(
+    conn: &mut PgConnection,
+    uid: i32,
+    content_hash: &str,
+) -> QueryResult<Option<i32>> {
     use crate::schema::photos;
 
-    let mut photo: Photo = diesel::insert_into(photos::table)
-        .values(NewPhoto::from_form(&photo_form, uid))
-        .returning(Photo::as_returning())
-        .get_result(&mut connection())?;
+    photos::table
+        .filter(photos::user_id.eq(uid))
+        .filter(photos::content_hash.eq(content_hash))
+        .select(photos::id)
+        .first(conn)
+        .optional()
+}
 
-    let file_content = photo_form.photo_image.contents.clone();
+/// Делает из исходного файла JPEG для показа и ML, миниатюру и превью,
+/// считает CLIP эмбеддинг и находит лица.
+///
+/// Повторный запуск после частичной ошибки безопасен: лица не добавляются,
+/// если у изображения они уже есть.
+pub async fn process_photo(
+    pool: &DbPool,
+    ml: &dyn MlBackend,
+    storage: Arc<dyn Storage>,
+    config: Arc<Config>,
+    photo_id: i32,
+) -> Result<(), CreatePhotoError> {
+    use crate::schema::photos;
 
-    let file_path = format!("{UPLOAD_DIR_IMAGES}/{}.jpeg", photo.id);
+    let original_path: Option<String> = interact_with(pool, move |conn| {
+        Ok::<_, CreatePhotoError>(
+            photos::table
+                .find(photo_id)
+                .select(photos::original_path)
+                .first(conn)?,
+        )
+    })
+    .await?;
+    let original_path = original_path.ok_or(CreatePhotoError::MissingOriginal(photo_id))?;
+    let file_content = storage.get(&original_path).await?;
+
+    let file_path = format!("{}/{photo_id}.jpeg", config.storage.images_dir);
+
+    let blocking_config = config.clone();
+    let (raw_image, saved_image, exif, (renditions, rendition_files), perceptual_hash) =
+        tokio::task::spawn_blocking(move || -> Result<_, CreatePhotoError> {
+            let exif = read_exif(&file_content);
+            let raw_image = decode_oriented(file_content, exif.orientation)?;
+            let saved_image = encode_jpeg(&raw_image, JPEG_QUALITY)?;
+            let renditions = encode_renditions(
+                &raw_image,
+                photo_id,
+                &blocking_config.storage.renditions_dir,
+                &blocking_config.renditions,
+            )?;
+            let perceptual_hash = perceptual_hash(&raw_image);
+            Ok((raw_image, saved_image, exif, renditions, perceptual_hash))
+        })
+        .await??;
+
+    storage
+        .put(&file_path, saved_image.clone(), "image/jpeg")
+        .await?;
+    put_files(storage.as_ref(), rendition_files).await?;
+
+    let embedding = Vector::from(ml.clip_visual(saved_image.clone()).await?);
+    let faces = ml.faces_recognition(saved_image).await?;
+
+    let face_files = interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            let photo: Photo = diesel::update(photos::table.find(photo_id))
+                .set((
+                    photos::path.eq(&file_path),
+                    photos::embedding.eq(embedding),
+                    photos::perceptual_hash.eq(perceptual_hash),
+                    &exif,
+                    &renditions,
+                ))
+                .returning(Photo::as_returning())
+                .get_result(conn)?;
+
+            let face_files = cut_faces_and_save(conn, &config, &photo, &raw_image, faces)?;
+            refresh_smart_albums_for_photo(conn, photo_id)?;
+            Ok::<_, CreatePhotoError>(face_files)
+        })
+    })
+    .await?;
+
+    // Лица пишутся в хранилище после транзакции. Если запись не удалась,
+    // задача повторится, и файлы лиц будут записаны снова.
+    put_files(storage.as_ref(), face_files).await?;
+    Ok(())
+}
 
-    let dyn_img = ImageReader::new(Cursor::new(file_content))
-        .with_guessed_format()?
-        .decode()?;
+/// Заново делает миниатюру и превью уже обработанного изображения. Источник -
+/// исходный файл, а для загруженных до его сохранения изображений - JPEG для
+/// показа, который уже повёрнут по EXIF.
+pub async fn generate_renditions(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    config: Arc<Config>,
+    photo_id: i32,
+) -> Result<(), CreatePhotoError> {
+    use crate::schema::photos;
 
-    dyn_img.save(&file_path)?;
+    let (original_path, path, orientation): (Option<String>, Option<String>, Option<i16>) =
+        interact_with(pool, move |conn| {
+            Ok::<_, CreatePhotoError>(
+                photos::table
+                    .find(photo_id)
+                    .select((photos::original_path, photos::path, photos::orientation))
+                    .first(conn)?,
+            )
+        })
+        .await?;
 
-    photo = diesel::update(photos::table.find(photo.id))
-        .set((
-            photos::path.eq(&file_path),
-            photos::embedding.eq(Vector::from(clip_visual_from_ml(&file_path).await?)),
-        ))
-        .returning(Photo::as_returning())
-        .get_result(&mut connection())?;
+    let (source_path, orientation) = match (original_path, path) {
+        (Some(original_path), _) => (original_path, orientation),
+        (None, Some(path)) => (path, None),
+        (None, None) => return Err(CreatePhotoError::MissingOriginal(photo_id)),
+    };
+    let file_content = storage.get(&source_path).await?;
+
+    let (renditions, files) =
+        tokio::task::spawn_blocking(move || -> Result<_, CreatePhotoError> {
+            let raw_image = decode_oriented(file_content, orientation)?;
+            Ok(encode_renditions(
+                &raw_image,
+                photo_id,
+                &config.storage.renditions_dir,
+                &config.renditions,
+            )?)
+        })
+        .await??;
+    put_files(storage, files).await?;
+
+    interact_with(pool, move |conn| {
+        diesel::update(photos::table.find(photo_id))
+            .set(&renditions)
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
 
-    cut_faces_and_save(photo).await?;
+async fn put_files(storage: &dyn Storage, files: Vec<EncodedFile>) -> Result<(), StorageError> {
+    try_join_all(files.into_iter().map(|file| async move {
+        storage
+            .put(&file.key, file.content, file.content_type)
+            .await
+    }))
+    .await?;
     Ok(())
 }
 
-pub async fn cut_faces_and_save(photo: Photo) -> Result<(), CreatePhotoError> {
+/// Декодирует файл в RGB с учётом ориентации EXIF
+fn decode_oriented(
+    file_content: Vec<u8>,
+    orientation: Option<i16>,
+) -> Result<RgbImage, CreatePhotoError> {
+    let dyn_img = ImageReader::new(Cursor::new(file_content))
+        .with_guessed_format()?
+        .decode()?;
+    Ok(flatten_to_rgb(&apply_orientation(dyn_img, orientation)))
+}
+
+/// Добавляет лица изображения и возвращает их вырезанные изображения, которые
+/// записываются в хранилище уже после транзакции. Если лица у изображения уже
+/// есть, новые не добавляются, а возвращаются файлы существующих лиц.
+pub fn cut_faces_and_save(
+    conn: &mut PgConnection,
+    config: &Config,
+    photo: &Photo,
+    raw_image: &RgbImage,
+    faces: Vec<RecognizedFaceOutput>,
+) -> Result<Vec<EncodedFile>, CreatePhotoError> {
     use crate::schema::{faces, persons};
 
-    let raw_image = image::open(photo.path.as_ref().unwrap())?.to_rgb8();
-    let faces = faces_recognition_from_ml(&photo.path.unwrap()).await?;
+    let existing_faces: i64 = faces::table
+        .filter(faces::photo_id.eq(photo.id))
+        .count()
+        .get_result(conn)?;
+    if existing_faces > 0 {
+        let stored: Vec<(String, Vec<Option<i32>>)> = faces::table
+            .filter(faces::photo_id.eq(photo.id))
+            .filter(faces::path.is_not_null())
+            .filter(faces::bbox.is_not_null())
+            .select((faces::path.assume_not_null(), faces::bbox.assume_not_null()))
+            .load(conn)?;
+        return Ok(stored
+            .into_iter()
+            .filter_map(|(path, bbox)| Some((path, stored_bbox(&bbox)?)))
+            .map(|(path, bbox)| face_file(raw_image, path, &bbox))
+            .collect::<Result<_, _>>()?);
+    }
 
+    let mut files = Vec::new();
     for face in faces {
         let db_face: Face = diesel::insert_into(faces::table)
             .values(&NewFace { photo_id: photo.id })
             .returning(Face::as_returning())
-            .get_result(&mut connection())?;
+            .get_result(conn)?;
 
-        let image_face_path = format!("{UPLOAD_DIR_FACES}/{}.jpeg", db_face.id);
+        let image_face_path = format!("{}/{}.jpeg", config.storage.faces_dir, db_face.id);
         let pg_vector_embedding = Vector::from(face.embedding);
 
-        cut_image(&raw_image, &face.bbox).save(&image_face_path)?;
-
-        let nearest_face: Option<Face> = faces::table
-            .select(Face::as_select())
-            .filter(faces::embedding.is_not_null())
-            .filter(faces::person_id.is_not_null())
-            .filter(
-                faces::embedding
-                    .cosine_distance(pg_vector_embedding.clone())
-                    .le(0.5),
-            )
-            .order(faces::embedding.cosine_distance(pg_vector_embedding.clone()))
-            .first(&mut connection())
-            .optional()?;
-
-        let person_id = match nearest_face {
-            Some(person_face) => person_face.person_id.unwrap(),
-            None => {
+        files.push(face_file(raw_image, image_face_path.clone(), &face.bbox)?);
+
+        let (person_id, is_ignored) = match match_face(
+            conn,
+            photo.user_id,
+            &pg_vector_embedding,
+            config.recognition.face_distance_threshold,
+        )? {
+            FaceMatch::Person(person_id) => (Some(person_id), false),
+            FaceMatch::Ignored => (None, true),
+            FaceMatch::Unknown => {
                 let new_person = NewPerson {
                     title: "Unknown".to_string(),
-                    avatar: image_face_path,
+                    avatar: image_face_path.clone(),
+                    user_id: photo.user_id,
                 };
-                diesel::insert_into(persons::table)
+                let person_id = diesel::insert_into(persons::table)
                     .values(&new_person)
                     .returning(persons::id)
-                    .get_result::<i32>(&mut connection())?
+                    .get_result::<i32>(conn)?;
+                (Some(person_id), false)
             }
         };
 
         diesel::update(faces::table.find(db_face.id))
             .set((
-                faces::path.eq(format!("{UPLOAD_DIR_FACES}/{}.jpeg", db_face.id)),
+                faces::path.eq(image_face_path),
                 faces::bbox.eq(Some(face.bbox.map(|el| Some(el as i32)).to_vec())),
                 faces::embedding.eq(Some(pg_vector_embedding)),
                 faces::person_id.eq(person_id),
+                faces::is_ignored.eq(is_ignored),
             ))
-            .execute(&mut connection())?;
+            .execute(conn)?;
     }
 
-    Ok(())
+    Ok(files)
 }
 
-fn cut_image(image: &RgbImage, bb: &[f32; 4]) -> DynamicImage {
-    let (x_tl, y_tl, x_br, y_br) = (bb[0], bb[1], bb[2], bb[3]);
-
-    let rect_width = x_br - x_tl;
-    let rect_height = y_br - y_tl;
+/// Вырезанное из изображения лицо
+fn face_file(
+    raw_image: &RgbImage,
+    key: String,
+    bbox: &[f32; 4],
+) -> Result<EncodedFile, image::ImageError> {
+    Ok(EncodedFile {
+        key,
+        content: encode_jpeg(&cut_image(raw_image, bbox), JPEG_QUALITY)?,
+        content_type: "image/jpeg",
+    })
+}
 
-    DynamicImage::from(
-        image::imageops::crop_imm(
-            image,
-            x_tl as u32,
-            y_tl as u32,
-            rect_width as u32,
-            rect_height as u32,
-        )
-        .to_image(),
-    )
+/// Рамка лица в том виде, в котором она хранится в базе
+fn stored_bbox(bbox: &[Option<i32>]) -> Option<[f32; 4]> {
+    match bbox {
+        [Some(x_tl), Some(y_tl), Some(x_br), Some(y_br)] => {
+            Some([*x_tl as f32, *y_tl as f32, *x_br as f32, *y_br as f32])
+        }
+        _ => None,
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct RecognizedFaceOutput {
-    pub score: f32,
-    pub bbox: [f32; 4],
-    pub landmarks: [(f32, f32); 5],
-    pub embedding: Vec<f32>,
+pub enum FaceMatch {
+    /// Лицо относится к существующей личности
+    Person(i32),
+    /// Лицо похоже на лица, помеченные как "не лицо"
+    Ignored,
+    /// Похожих лиц нет, нужна новая личность
+    Unknown,
 }
 
-async fn faces_recognition_from_ml(
-    path: &str,
-) -> Result<Vec<RecognizedFaceOutput>, CreatePhotoError> {
-    let form_data = multipart::Form::new().file("image", path).await?;
-
-    let response_body = reqwest::Client::new()
-        .post("http://0.0.0.0:3003/recognition-faces")
-        .multipart(form_data)
-        .send()
-        .await?
-        .text()
-        .await?;
+/// Подбирает личность пользователя `user_id` для эмбеддинга лица по ближайшим
+/// лицам с учётом ручных исправлений: личность пропускается, если из неё было
+/// убрано лицо, которое ближе к эмбеддингу, чем найденное лицо этой личности.
+pub fn match_face(
+    conn: &mut PgConnection,
+    user_id: i32,
+    embedding: &Vector,
+    threshold: f64,
+) -> Result<FaceMatch, diesel::result::Error> {
+    use crate::schema::{face_rejections, faces, photos};
+
+    let distance = || faces::embedding.cosine_distance(embedding.clone());
+
+    let candidates: Vec<(Option<i32>, f64)> = nearest_faces(
+        conn,
+        Owner::User(user_id),
+        embedding,
+        threshold,
+        FACE_MATCH_CANDIDATES,
+    )?
+    .into_iter()
+    .map(|(face, distance)| (face.person_id, distance))
+    .collect();
+
+    let nearest_ignored: Option<Option<f64>> = faces::table
+        .select(distance())
+        .filter(faces::embedding.is_not_null())
+        .filter(faces::is_ignored.eq(true))
+        .filter(
+            faces::photo_id.eq_any(
+                photos::table
+                    .select(photos::id)
+                    .filter(photos::user_id.eq(user_id)),
+            ),
+        )
+        .filter(distance().le(threshold))
+        .order(distance())
+        .first(conn)
+        .optional()?;
+
+    if let Some(Some(ignored_distance)) = nearest_ignored {
+        match candidates.first() {
+            Some((_, candidate_distance)) if *candidate_distance <= ignored_distance => {}
+            _ => return Ok(FaceMatch::Ignored),
+        }
+    }
 
-    Ok(serde_json::from_str(&response_body)?)
+    for (person_id, candidate_distance) in candidates {
+        let Some(person_id) = person_id else {
+            continue;
+        };
+
+        let closer_rejections: i64 = face_rejections::table
+            .inner_join(faces::table)
+            .filter(face_rejections::person_id.eq(person_id))
+            .filter(distance().lt(candidate_distance))
+            .count()
+            .get_result(conn)?;
+
+        if closer_rejections == 0 {
+            return Ok(FaceMatch::Person(person_id));
+        }
+    }
+
+    Ok(FaceMatch::Unknown)
 }
 
-pub async fn clip_textual_from_ml(text: String) -> Result<Vec<f32>, CreatePhotoError> {
-    let response_body = reqwest::Client::new()
-        .post("http://0.0.0.0:3003/clip-textual")
-        .query(&[("text", &text)])
-        .send()
-        .await?
-        .text()
-        .await?;
+/// Ближайшие к эмбеддингу лица, назначенные личностям `owner`, в порядке
+/// возрастания косинусного расстояния
+pub fn nearest_faces(
+    conn: &mut PgConnection,
+    owner: Owner,
+    embedding: &Vector,
+    max_distance: f64,
+    limit: i64,
+) -> Result<Vec<(ListFace, f64)>, diesel::result::Error> {
+    use crate::schema::{faces, persons};
+
+    let distance = || faces::embedding.cosine_distance(embedding.clone());
+
+    let mut query = faces::table
+        .select((ListFace::as_select(), distance()))
+        .filter(faces::embedding.is_not_null())
+        .filter(faces::person_id.is_not_null())
+        .filter(faces::is_ignored.eq(false))
+        .filter(distance().le(max_distance))
+        .order(distance())
+        .limit(limit)
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(
+            faces::person_id.eq_any(
+                persons::table
+                    .select(persons::id.nullable())
+                    .filter(persons::user_id.eq(user_id)),
+            ),
+        );
+    }
+
+    let rows: Vec<(ListFace, Option<f64>)> = query.load(conn)?;
 
-    Ok(serde_json::from_str(&response_body)?)
+    Ok(rows
+        .into_iter()
+        .map(|(face, distance)| (face, distance.unwrap_or_default()))
+        .collect())
 }
 
-pub async fn clip_visual_from_ml(path: &str) -> Result<Vec<f32>, CreatePhotoError> {
-    let form_data = multipart::Form::new().file("image", path).await?;
+fn cut_image(image: &RgbImage, bb: &[f32; 4]) -> RgbImage {
+    let (x_tl, y_tl, x_br, y_br) = (bb[0], bb[1], bb[2], bb[3]);
 
-    let response_body = reqwest::Client::new()
-        .post("http://0.0.0.0:3003/clip-visual")
-        .multipart(form_data)
-        .send()
-        .await?
-        .text()
-        .await?;
+    let rect_width = x_br - x_tl;
+    let rect_height = y_br - y_tl;
 
-    Ok(serde_json::from_str(&response_body)?)
+    image::imageops::crop_imm(
+        image,
+        x_tl as u32,
+        y_tl as u32,
+        rect_width as u32,
+        rect_height as u32,
+    )
+    .to_image()
 }
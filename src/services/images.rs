@@ -0,0 +1,179 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{ColorType, DynamicImage, ImageResult, Rgb, RgbImage};
+
+use crate::config::RenditionsConfig;
+use crate::models::PhotoRenditions;
+
+/// Поворачивает и отражает изображение согласно тегу EXIF Orientation,
+/// чтобы производные изображения отображались без учёта EXIF
+pub fn apply_orientation(image: DynamicImage, orientation: Option<i16>) -> DynamicImage {
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// RGB изображение для JPEG: прозрачные области накладываются на белый фон
+pub fn flatten_to_rgb(image: &DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| {
+            ((u16::from(channel) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8
+        };
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+/// Перцептивный хэш (dHash): 64 бита сравнения соседних пикселей уменьшенной
+/// серой копии. У почти одинаковых изображений хэши различаются в немногих битах.
+pub fn perceptual_hash(image: &RgbImage) -> i64 {
+    let gray = imageops::grayscale(image);
+    let small = imageops::resize(&gray, 9, 8, FilterType::Triangle);
+
+    let mut hash: u64 = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash as i64
+}
+
+/// Закодированный файл, который нужно положить в хранилище под ключом `key`
+pub struct EncodedFile {
+    pub key: String,
+    pub content: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+/// Кодирует квадратную миниатюру и превью изображения с ключами в `dir`, а
+/// при включённом `config.webp` - ещё и их копии в WebP
+pub fn encode_renditions(
+    image: &RgbImage,
+    photo_id: i32,
+    dir: &str,
+    config: &RenditionsConfig,
+) -> ImageResult<(PhotoRenditions, Vec<EncodedFile>)> {
+    let thumbnail = square_thumbnail(image, config.thumbnail_size);
+    let preview = fit_within(image, config.preview_size);
+
+    let thumbnail_path = format!("{dir}/{photo_id}_thumb.jpeg");
+    let preview_path = format!("{dir}/{photo_id}_preview.jpeg");
+    let mut files = vec![
+        EncodedFile {
+            key: thumbnail_path.clone(),
+            content: encode_jpeg(&thumbnail, config.jpeg_quality)?,
+            content_type: "image/jpeg",
+        },
+        EncodedFile {
+            key: preview_path.clone(),
+            content: encode_jpeg(&preview, config.jpeg_quality)?,
+            content_type: "image/jpeg",
+        },
+    ];
+
+    let (thumbnail_webp_path, preview_webp_path) = if config.webp {
+        let thumbnail_webp_path = format!("{dir}/{photo_id}_thumb.webp");
+        let preview_webp_path = format!("{dir}/{photo_id}_preview.webp");
+        files.push(EncodedFile {
+            key: thumbnail_webp_path.clone(),
+            content: encode_webp(&thumbnail)?,
+            content_type: "image/webp",
+        });
+        files.push(EncodedFile {
+            key: preview_webp_path.clone(),
+            content: encode_webp(&preview)?,
+            content_type: "image/webp",
+        });
+        (Some(thumbnail_webp_path), Some(preview_webp_path))
+    } else {
+        (None, None)
+    };
+
+    let renditions = PhotoRenditions {
+        thumbnail_path: Some(thumbnail_path),
+        preview_path: Some(preview_path),
+        thumbnail_webp_path,
+        preview_webp_path,
+    };
+    Ok((renditions, files))
+}
+
+/// Кодирует аватар: центральный квадрат со стороной миниатюры в JPEG
+pub fn encode_avatar(image: &RgbImage, config: &RenditionsConfig) -> ImageResult<Vec<u8>> {
+    encode_jpeg(
+        &square_thumbnail(image, config.thumbnail_size),
+        config.jpeg_quality,
+    )
+}
+
+/// Центральный квадрат изображения, уменьшенный до `size`. Маленькие
+/// изображения не увеличиваются.
+fn square_thumbnail(image: &RgbImage, size: u32) -> RgbImage {
+    let side = image.width().min(image.height());
+    let square = imageops::crop_imm(
+        image,
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    )
+    .to_image();
+
+    if side <= size {
+        square
+    } else {
+        imageops::resize(&square, size, size, FilterType::Lanczos3)
+    }
+}
+
+/// Изображение, уменьшенное с сохранением пропорций так, чтобы большая
+/// сторона не превышала `size`
+fn fit_within(image: &RgbImage, size: u32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    if width.max(height) <= size {
+        return image.clone();
+    }
+
+    let scale = f64::from(size) / f64::from(width.max(height));
+    let new_width = ((f64::from(width) * scale).round() as u32).max(1);
+    let new_height = ((f64::from(height) * scale).round() as u32).max(1);
+    imageops::resize(image, new_width, new_height, FilterType::Lanczos3)
+}
+
+pub fn encode_jpeg(image: &RgbImage, quality: u8) -> ImageResult<Vec<u8>> {
+    let mut content = Vec::new();
+    JpegEncoder::new_with_quality(&mut content, quality).encode(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        ColorType::Rgb8,
+    )?;
+    Ok(content)
+}
+
+fn encode_webp(image: &RgbImage) -> ImageResult<Vec<u8>> {
+    let mut content = Vec::new();
+    WebPEncoder::new_lossless(&mut content).encode(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        ColorType::Rgb8,
+    )?;
+    Ok(content)
+}
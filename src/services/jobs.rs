@@ -0,0 +1,214 @@
+use chrono::{Duration, Utc};
+use diesel::{
+    sql_types::Text, BoolExpressionMethods, Connection, ExpressionMethods, IntoSql,
+    NullableExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+    SelectableHelper,
+};
+
+use crate::db_connection::{interact, DbPool};
+use crate::errors::DbError;
+use crate::models::{Job, JobKind, JobStatus, NewJob, Owner};
+
+/// Через сколько задача в состоянии `running` считается брошенной
+const JOB_LEASE_SECS: i64 = 10 * 60;
+/// Ошибка задачи, обработчик которой упал на последней попытке
+const LEASE_EXPIRED_ERROR: &str = "lease expired";
+const RETRY_BASE_DELAY_SECS: i64 = 10;
+const RETRY_MAX_DELAY_SECS: i64 = 60 * 60;
+
+pub fn enqueue_job(conn: &mut PgConnection, new_job: NewJob) -> Result<Job, diesel::result::Error> {
+    diesel::insert_into(crate::schema::jobs::table)
+        .values(&new_job)
+        .returning(Job::as_returning())
+        .get_result(conn)
+}
+
+pub async fn get_job_by_id(pool: &DbPool, owner: Owner, job_id: i32) -> Result<Job, DbError> {
+    use crate::schema::{jobs, photos};
+
+    let mut query = jobs::table
+        .find(job_id)
+        .select(Job::as_select())
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(
+            jobs::photo_id.eq_any(
+                photos::table
+                    .select(photos::id.nullable())
+                    .filter(photos::user_id.eq(user_id)),
+            ),
+        );
+    }
+
+    interact(pool, move |conn| query.first(conn)).await
+}
+
+/// Перезапускает упавшую задачу с новым счётчиком попыток
+pub async fn retry_job(pool: &DbPool, owner: Owner, job_id: i32) -> Result<Job, DbError> {
+    use crate::schema::{jobs, photos};
+
+    let mut query = diesel::update(
+        jobs::table
+            .find(job_id)
+            .filter(jobs::status.eq(JobStatus::Failed)),
+    )
+    .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(
+            jobs::photo_id.eq_any(
+                photos::table
+                    .select(photos::id.nullable())
+                    .filter(photos::user_id.eq(user_id)),
+            ),
+        );
+    }
+
+    interact(pool, move |conn| {
+        query
+            .set((
+                jobs::status.eq(JobStatus::Pending),
+                jobs::attempts.eq(0),
+                jobs::run_at.eq(Utc::now()),
+                jobs::updated_at.eq(Utc::now()),
+            ))
+            .returning(Job::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+/// Забирает следующую готовую к запуску задачу, в том числе брошенную
+/// упавшим обработчиком. Параллельные обработчики не получат одну задачу
+/// благодаря `FOR UPDATE SKIP LOCKED`. Брошенные задачи без оставшихся
+/// попыток помечаются упавшими, чтобы их можно было перезапустить.
+pub fn claim_next_job(conn: &mut PgConnection) -> Result<Option<Job>, diesel::result::Error> {
+    use crate::schema::jobs;
+
+    conn.transaction(|conn| {
+        let now = Utc::now();
+        let lease_expired = jobs::status
+            .eq(JobStatus::Running)
+            .and(jobs::locked_at.lt(now - Duration::seconds(JOB_LEASE_SECS)));
+
+        diesel::update(jobs::table)
+            .filter(lease_expired)
+            .filter(jobs::attempts.ge(jobs::max_attempts))
+            .set((
+                jobs::status.eq(JobStatus::Failed),
+                jobs::locked_at.eq(None::<chrono::DateTime<Utc>>),
+                jobs::last_error.eq(LEASE_EXPIRED_ERROR),
+                jobs::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        let job: Option<Job> = jobs::table
+            .filter(
+                jobs::status
+                    .eq(JobStatus::Pending)
+                    .and(jobs::run_at.le(now))
+                    .or(lease_expired.and(jobs::attempts.lt(jobs::max_attempts))),
+            )
+            .order(jobs::run_at)
+            .select(Job::as_select())
+            .for_update()
+            .skip_locked()
+            .first(conn)
+            .optional()?;
+
+        let Some(job) = job else {
+            return Ok(None);
+        };
+
+        diesel::update(jobs::table.find(job.id))
+            .set((
+                jobs::status.eq(JobStatus::Running),
+                jobs::attempts.eq(jobs::attempts + 1),
+                jobs::locked_at.eq(now),
+                jobs::updated_at.eq(now),
+            ))
+            .returning(Job::as_returning())
+            .get_result(conn)
+            .map(Some)
+    })
+}
+
+pub fn complete_job(conn: &mut PgConnection, job_id: i32) -> Result<(), diesel::result::Error> {
+    use crate::schema::jobs;
+
+    diesel::update(jobs::table.find(job_id))
+        .set((
+            jobs::status.eq(JobStatus::Done),
+            jobs::locked_at.eq(None::<chrono::DateTime<Utc>>),
+            jobs::last_error.eq(None::<String>),
+            jobs::updated_at.eq(Utc::now()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Откладывает задачу с экспоненциальной задержкой или помечает её упавшей,
+/// если попытки закончились
+pub fn fail_job(
+    conn: &mut PgConnection,
+    job: &Job,
+    error: &str,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::jobs;
+
+    let now = Utc::now();
+    let status = if job.attempts >= job.max_attempts {
+        JobStatus::Failed
+    } else {
+        JobStatus::Pending
+    };
+
+    diesel::update(jobs::table.find(job.id))
+        .set((
+            jobs::status.eq(status),
+            jobs::run_at.eq(now + retry_delay(job.attempts)),
+            jobs::locked_at.eq(None::<chrono::DateTime<Utc>>),
+            jobs::last_error.eq(error),
+            jobs::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 20) as u32 - 1;
+    let delay = RETRY_BASE_DELAY_SECS.saturating_mul(2_i64.saturating_pow(exponent));
+    Duration::seconds(delay.min(RETRY_MAX_DELAY_SECS))
+}
+
+/// Ставит задачи на создание миниатюр и превью для обработанных изображений,
+/// у которых их нет, или для всех обработанных изображений при `all`.
+/// Изображения, для которых такая задача уже ждёт запуска, пропускаются.
+pub async fn enqueue_renditions_backfill(pool: &DbPool, all: bool) -> Result<usize, DbError> {
+    use crate::schema::{jobs, photos};
+
+    let queued = jobs::table
+        .select(jobs::photo_id)
+        .filter(jobs::kind.eq(JobKind::GenerateRenditions))
+        .filter(jobs::photo_id.is_not_null())
+        .filter(jobs::status.eq_any([JobStatus::Pending, JobStatus::Running]));
+
+    let mut query = photos::table
+        .select((
+            JobKind::GenerateRenditions.into_sql::<Text>(),
+            photos::id.nullable(),
+        ))
+        .filter(photos::path.is_not_null())
+        .filter(photos::id.nullable().ne_all(queued))
+        .into_boxed();
+    if !all {
+        query = query.filter(photos::thumbnail_path.is_null());
+    }
+
+    interact(pool, move |conn| {
+        diesel::insert_into(jobs::table)
+            .values(query)
+            .into_columns((jobs::kind, jobs::photo_id))
+            .execute(conn)
+    })
+    .await
+}
@@ -0,0 +1,369 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderName, HeaderValue, Response, StatusCode},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use diesel::{
+    dsl::exists, BoolExpressionMethods, ExpressionMethods, PgConnection, QueryDsl, QueryResult,
+    RunQueryDsl, SelectableHelper,
+};
+use hmac::{Hmac, Mac};
+use httpdate::{fmt_http_date, HttpDate};
+use sha2::Sha256;
+
+use crate::{
+    config::Config,
+    db_connection::{interact, DbPool},
+    errors::{MediaError, StorageError},
+    models::{MediaVariant, Owner, Photo, SignedMediaQuery, SignedMediaUrl},
+    storage::{ByteRange, Storage},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Адрес файла фотографии, он же подписывается в подписанных ссылках
+pub fn photo_media_path(photo_id: i32, variant: MediaVariant) -> String {
+    format!("/api/media/photo/{photo_id}/{}", variant.as_str())
+}
+
+/// Адрес вырезанного лица
+pub fn face_media_path(face_id: i32) -> String {
+    format!("/api/media/face/{face_id}")
+}
+
+/// Адрес аватара пользователя
+pub fn avatar_media_path(user_id: i32) -> String {
+    format!("/api/media/avatar/{user_id}")
+}
+
+/// Адрес файла фотографии публичной ссылки `token`
+pub fn shared_photo_media_path(token: &str, photo_id: i32, variant: MediaVariant) -> String {
+    format!(
+        "/api/public/{token}/photos/{photo_id}/media/{}",
+        variant.as_str()
+    )
+}
+
+/// Ссылка на `path`, открывающаяся без авторизации до истечения подписи
+pub fn sign_media_path(path: &str, config: &Config) -> SignedMediaUrl {
+    let expires_at = Utc::now() + Duration::seconds(config.media.signed_url_lifetime_secs);
+    let expires = expires_at.timestamp();
+    let signature =
+        URL_SAFE_NO_PAD.encode(media_mac(path, expires, config).finalize().into_bytes());
+
+    SignedMediaUrl {
+        url: format!("{path}?expires={expires}&signature={signature}"),
+        expires_at,
+    }
+}
+
+/// Ссылка на файл `key` для тегов `<img>`: подписанная ссылка самого
+/// хранилища, если оно их выдаёт, иначе подписанный адрес `path` сервера
+pub fn signed_media_url(
+    storage: &dyn Storage,
+    key: &str,
+    path: &str,
+    config: &Config,
+) -> SignedMediaUrl {
+    let lifetime = config.media.signed_url_lifetime_secs;
+    let presigned = storage.presign(key, std::time::Duration::from_secs(lifetime as u64));
+
+    match presigned {
+        Some(url) => SignedMediaUrl {
+            url,
+            expires_at: Utc::now() + Duration::seconds(lifetime),
+        },
+        None => sign_media_path(path, config),
+    }
+}
+
+/// Проверяет подпись ссылки на `path` и срок её действия
+pub fn verify_media_signature(
+    path: &str,
+    signed: &SignedMediaQuery,
+    config: &Config,
+) -> Result<(), MediaError> {
+    let (Some(expires), Some(signature)) = (signed.expires, &signed.signature) else {
+        return Err(MediaError::InvalidSignature);
+    };
+    if expires < Utc::now().timestamp() {
+        return Err(MediaError::InvalidSignature);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| MediaError::InvalidSignature)?;
+
+    media_mac(path, expires, config)
+        .verify_slice(&signature)
+        .map_err(|_| MediaError::InvalidSignature)
+}
+
+fn media_mac(path: &str, expires: i64, config: &Config) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(config.media.signing_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{path}\n{expires}").as_bytes());
+    mac
+}
+
+/// Ключ файла фотографии, видимой `owner`
+pub async fn get_photo_file(
+    pool: &DbPool,
+    owner: Owner,
+    photo_id: i32,
+    variant: MediaVariant,
+) -> Result<String, MediaError> {
+    let photo = interact(pool, move |conn| find_visible_photo(conn, owner, photo_id)).await?;
+    variant.file_path(photo).ok_or(MediaError::NotFound)
+}
+
+/// Ключ вырезанного лица с фотографии, видимой `owner`
+pub async fn get_face_file(
+    pool: &DbPool,
+    owner: Owner,
+    face_id: i32,
+) -> Result<String, MediaError> {
+    use crate::schema::faces;
+
+    let face_path = interact(pool, move |conn| {
+        let (photo_id, face_path): (i32, Option<String>) = faces::table
+            .find(face_id)
+            .select((faces::photo_id, faces::path))
+            .first(conn)?;
+        find_visible_photo(conn, owner, photo_id)?;
+        Ok(face_path)
+    })
+    .await?;
+    face_path.ok_or(MediaError::NotFound)
+}
+
+/// Ключ аватара пользователя, аватары видны всем пользователям
+pub async fn get_avatar_file(pool: &DbPool, user_id: i32) -> Result<String, MediaError> {
+    use crate::schema::users;
+
+    let avatar: Option<String> = interact(pool, move |conn| {
+        users::table.find(user_id).select(users::avatar).first(conn)
+    })
+    .await?;
+    avatar.ok_or(MediaError::NotFound)
+}
+
+/// Фотография `owner` или фотография из его альбома либо альбома, которым с
+/// ним поделились
+pub fn find_visible_photo(
+    conn: &mut PgConnection,
+    owner: Owner,
+    photo_id: i32,
+) -> QueryResult<Photo> {
+    use crate::schema::{album_photos, album_shares, albums, photos};
+
+    let photo: Photo = photos::table
+        .find(photo_id)
+        .select(Photo::as_select())
+        .first(conn)?;
+    let Some(user_id) = owner.user_id() else {
+        return Ok(photo);
+    };
+    if photo.user_id == user_id {
+        return Ok(photo);
+    }
+
+    let in_visible_album = diesel::select(exists(
+        album_photos::table
+            .filter(album_photos::photo_id.eq(photo_id))
+            .filter(
+                album_photos::album_id
+                    .eq_any(
+                        albums::table
+                            .select(albums::id)
+                            .filter(albums::user_id.eq(user_id)),
+                    )
+                    .or(album_photos::album_id.eq_any(
+                        album_shares::table
+                            .select(album_shares::album_id)
+                            .filter(album_shares::user_id.eq(user_id)),
+                    )),
+            ),
+    ))
+    .get_result(conn)?;
+
+    match in_visible_album {
+        true => Ok(photo),
+        false => Err(diesel::result::Error::NotFound),
+    }
+}
+
+/// Отдаёт файл хранилища с поддержкой Range и условных запросов по `ETag` и
+/// `Last-Modified`
+pub async fn serve_media_file(
+    storage: &dyn Storage,
+    key: &str,
+    req: Request,
+    config: &Config,
+) -> Result<Response<Body>, MediaError> {
+    let headers = req.into_parts().0.headers;
+    let request_header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let range = request_header(header::RANGE).and_then(ByteRange::parse);
+
+    let object = match storage.stream(key, range).await {
+        Err(StorageError::NotFound(_)) => return Err(MediaError::NotFound),
+        Err(StorageError::InvalidRange(size)) => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            insert_header(
+                &mut response,
+                header::CONTENT_RANGE,
+                format!("bytes */{size}"),
+            );
+            return Ok(response);
+        }
+        result => result?,
+    };
+
+    // If-Modified-Since учитывается только без If-None-Match
+    let not_modified = match request_header(header::IF_NONE_MATCH) {
+        Some(value) => object.etag.as_ref().is_some_and(|etag| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        }),
+        None => request_header(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.parse::<HttpDate>().ok())
+            .zip(object.last_modified)
+            .is_some_and(|(since, modified)| HttpDate::from(modified) <= since),
+    };
+
+    let mut response = match not_modified {
+        true => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            response
+        }
+        false => {
+            let content_length = object.content_length();
+            let mut response = Response::new(Body::from_stream(object.body));
+            insert_header(
+                &mut response,
+                header::CONTENT_LENGTH,
+                content_length.to_string(),
+            );
+            if let Some(content_type) = object.content_type {
+                insert_header(&mut response, header::CONTENT_TYPE, content_type);
+            }
+            if let Some((first, last)) = object.range {
+                *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                insert_header(
+                    &mut response,
+                    header::CONTENT_RANGE,
+                    format!("bytes {first}-{last}/{}", object.size),
+                );
+            }
+            response
+        }
+    };
+
+    insert_header(&mut response, header::ACCEPT_RANGES, "bytes".to_string());
+    if let Some(etag) = object.etag {
+        insert_header(&mut response, header::ETAG, etag);
+    }
+    if let Some(modified) = object.last_modified {
+        insert_header(
+            &mut response,
+            header::LAST_MODIFIED,
+            fmt_http_date(modified),
+        );
+    }
+    insert_header(
+        &mut response,
+        header::CACHE_CONTROL,
+        format!("private, max-age={}", config.media.cache_max_age_secs),
+    );
+
+    Ok(response)
+}
+
+fn insert_header(response: &mut Response<Body>, name: HeaderName, value: String) {
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.media.signing_secret = "media-test-secret".to_string();
+        config
+    }
+
+    /// Параметры подписи из адреса, выданного `sign_media_path`
+    fn signed_query(url: &str) -> SignedMediaQuery {
+        let (_, query) = url.split_once('?').unwrap();
+        let mut signed = SignedMediaQuery::default();
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name {
+                "expires" => signed.expires = Some(value.parse().unwrap()),
+                "signature" => signed.signature = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        signed
+    }
+
+    #[test]
+    fn signed_path_is_accepted() {
+        let config = config();
+        let path = photo_media_path(1, MediaVariant::Preview);
+        let signed = sign_media_path(&path, &config);
+
+        assert!(signed.url.starts_with(&path));
+        assert!(verify_media_signature(&path, &signed_query(&signed.url), &config).is_ok());
+    }
+
+    #[test]
+    fn signature_is_bound_to_path_and_secret() {
+        let config = config();
+        let path = photo_media_path(1, MediaVariant::Preview);
+        let signed = signed_query(&sign_media_path(&path, &config).url);
+
+        let other_path = photo_media_path(1, MediaVariant::Original);
+        assert!(verify_media_signature(&other_path, &signed, &config).is_err());
+
+        let mut other_config = config.clone();
+        other_config.media.signing_secret = "other-secret".to_string();
+        assert!(verify_media_signature(&path, &signed, &other_config).is_err());
+    }
+
+    #[test]
+    fn expired_or_tampered_signature_is_rejected() {
+        let config = config();
+        let path = face_media_path(7);
+        let signed = signed_query(&sign_media_path(&path, &config).url);
+
+        let moved_expiry = SignedMediaQuery {
+            expires: signed.expires.map(|expires| expires + 60),
+            signature: signed.signature.clone(),
+        };
+        assert!(verify_media_signature(&path, &moved_expiry, &config).is_err());
+
+        let expires = Utc::now().timestamp() - 1;
+        let signature =
+            URL_SAFE_NO_PAD.encode(media_mac(&path, expires, &config).finalize().into_bytes());
+        let expired = SignedMediaQuery {
+            expires: Some(expires),
+            signature: Some(signature),
+        };
+        assert!(verify_media_signature(&path, &expired, &config).is_err());
+
+        let garbage = SignedMediaQuery {
+            expires: signed.expires,
+            signature: Some("not base64!".to_string()),
+        };
+        assert!(verify_media_signature(&path, &garbage, &config).is_err());
+        assert!(verify_media_signature(&path, &SignedMediaQuery::default(), &config).is_err());
+    }
+}
@@ -1,4 +1,16 @@
 pub mod albums;
+pub mod api_keys;
+pub mod auth;
+pub mod duplicates;
+pub mod exif;
+pub mod faces;
 pub mod facial_recognition;
+pub mod images;
+pub mod jobs;
+pub mod media;
+pub mod pagination;
+pub mod persons;
 pub mod photos;
+pub mod share_links;
+pub mod timeline;
 pub mod users;
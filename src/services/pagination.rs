@@ -0,0 +1,367 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use diesel::{
+    dsl::sql,
+    expression::SqlLiteral,
+    pg::Pg,
+    sql_types::{Bool, Integer, Text},
+    BoxableExpression,
+};
+
+use crate::errors::PageError;
+use crate::models::{
+    AlbumSort, FaceSort, Page, PageParams, PersonSort, PhotoSort, SortDirection, UserSort,
+};
+
+const DEFAULT_PAGE_LIMIT: i64 = 50;
+const MAX_PAGE_LIMIT: i64 = 500;
+
+/// Поле сортировки списка
+pub trait SortField: Copy + PartialEq {
+    /// Колонка id таблицы, второй ключ сортировки
+    const ID_COLUMN: &'static str;
+
+    /// Имя поля в курсоре
+    fn name(&self) -> &'static str;
+    /// SQL выражение без NULL, по которому сортируется список
+    fn expression(&self) -> &'static str;
+    /// Тип выражения в Postgres для сравнения со значением из курсора
+    fn sql_type(&self) -> &'static str;
+    fn default_direction(&self) -> SortDirection;
+}
+
+/// Позиция после последней записи страницы
+#[derive(Clone, Debug, PartialEq)]
+enum Cursor {
+    /// Значение поля сортировки и id последней записи
+    After {
+        sort: String,
+        direction: SortDirection,
+        key: String,
+        id: i32,
+    },
+    /// Количество пропущенных записей, для сортировки не по полю
+    Offset(i64),
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let raw = match self {
+            Cursor::After {
+                sort,
+                direction,
+                key,
+                id,
+            } => format!("a:{sort}:{}:{id}:{key}", direction.as_str()),
+            Cursor::Offset(offset) => format!("o:{offset}"),
+        };
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    fn decode(cursor: &str) -> Result<Self, PageError> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|raw| String::from_utf8(raw).ok())
+            .ok_or(PageError::InvalidCursor)?;
+
+        match raw.split_once(':') {
+            Some(("o", offset)) => offset
+                .parse()
+                .ok()
+                .filter(|offset| *offset >= 0)
+                .map(Cursor::Offset)
+                .ok_or(PageError::InvalidCursor),
+            Some(("a", rest)) => {
+                let mut parts = rest.splitn(4, ':');
+                let (Some(sort), Some(direction), Some(id), Some(key)) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(PageError::InvalidCursor);
+                };
+                Ok(Cursor::After {
+                    sort: sort.to_string(),
+                    direction: SortDirection::parse(direction).ok_or(PageError::InvalidCursor)?,
+                    key: key.to_string(),
+                    id: id.parse().map_err(|_| PageError::InvalidCursor)?,
+                })
+            }
+            _ => Err(PageError::InvalidCursor),
+        }
+    }
+}
+
+/// Проверенные параметры страницы для списка, отсортированного по `S`.
+/// Без поля сортировки (`sort = None`) список упорядочивает сам запрос, а
+/// страницы листаются смещением.
+#[derive(Clone, Debug)]
+pub struct PageQuery<S: SortField> {
+    sort: Option<S>,
+    direction: SortDirection,
+    limit: i64,
+    offset: i64,
+    after: Option<(String, i32)>,
+}
+
+impl<S: SortField> PageQuery<S> {
+    pub fn new(params: &PageParams, sort: Option<S>) -> Result<Self, PageError> {
+        let direction = params
+            .direction
+            .or(sort.map(|sort| sort.default_direction()))
+            .unwrap_or_default();
+        let limit = params
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT);
+
+        let mut page = PageQuery {
+            sort,
+            direction,
+            limit,
+            offset: params.offset.unwrap_or(0).max(0),
+            after: None,
+        };
+
+        match params.cursor.as_deref().map(Cursor::decode).transpose()? {
+            None => {}
+            Some(Cursor::Offset(offset)) => page.offset = offset,
+            // Курсор подходит только к той сортировке, в которой был выдан
+            Some(Cursor::After {
+                sort: cursor_sort,
+                direction: cursor_direction,
+                key,
+                id,
+            }) => match sort {
+                Some(sort) if sort.name() == cursor_sort && direction == cursor_direction => {
+                    page.offset = 0;
+                    page.after = Some((key, id));
+                }
+                _ => return Err(PageError::InvalidCursor),
+            },
+        }
+
+        Ok(page)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit
+    }
+
+    /// Сколько записей запросить: на одну больше страницы, чтобы узнать,
+    /// есть ли следующая
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Значение поля сортировки в виде текста, выбирается вместе с записью
+    /// для курсора следующей страницы
+    pub fn key(&self) -> SqlLiteral<Text> {
+        match self.sort {
+            Some(sort) => sql(&format!("({})::text", sort.expression())),
+            None => sql("''"),
+        }
+    }
+
+    /// Порядок записей: поле сортировки, затем id
+    pub fn order(&self) -> SqlLiteral<Text> {
+        let direction = self.direction.as_sql();
+        match self.sort {
+            Some(sort) => sql(&format!(
+                "{} {direction}, {} {direction}",
+                sort.expression(),
+                S::ID_COLUMN
+            )),
+            None => sql(&format!("{} {direction}", S::ID_COLUMN)),
+        }
+    }
+
+    /// Условие "после курсора" для сортировки по полю
+    pub fn after<QS: 'static>(&self) -> Option<Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>>> {
+        let sort = self.sort?;
+        let (key, id) = self.after.clone()?;
+        let operator = match self.direction {
+            SortDirection::Asc => ">",
+            SortDirection::Desc => "<",
+        };
+
+        Some(Box::new(
+            sql::<Bool>(&format!(
+                "({}, {}) {operator} (CAST(",
+                sort.expression(),
+                S::ID_COLUMN
+            ))
+            .bind::<Text, _>(key)
+            .sql(&format!(" AS {}), ", sort.sql_type()))
+            .bind::<Integer, _>(id)
+            .sql(")"),
+        ))
+    }
+
+    /// Собирает страницу из `fetch_limit` записей со значениями `key`
+    pub fn finish<T>(&self, rows: Vec<(T, String)>, total: i64, id: impl Fn(&T) -> i32) -> Page<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        let mut rows = rows;
+        rows.truncate(self.limit as usize);
+
+        let next_cursor = match (has_more, self.sort, rows.last()) {
+            (true, Some(sort), Some((item, key))) => Some(Cursor::After {
+                sort: sort.name().to_string(),
+                direction: self.direction,
+                key: key.clone(),
+                id: id(item),
+            }),
+            (true, None, _) => Some(Cursor::Offset(self.offset + self.limit)),
+            _ => None,
+        };
+
+        Page {
+            items: rows.into_iter().map(|(item, _)| item).collect(),
+            total,
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
+        }
+    }
+}
+
+impl SortField for UserSort {
+    const ID_COLUMN: &'static str = "users.id";
+
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn expression(&self) -> &'static str {
+        match self {
+            UserSort::Id => "users.id",
+            UserSort::Username => "users.username",
+            UserSort::Email => "users.email",
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        match self {
+            UserSort::Id => "INT",
+            UserSort::Username | UserSort::Email => "TEXT",
+        }
+    }
+
+    fn default_direction(&self) -> SortDirection {
+        SortDirection::Asc
+    }
+}
+
+impl SortField for AlbumSort {
+    const ID_COLUMN: &'static str = "albums.id";
+
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn expression(&self) -> &'static str {
+        match self {
+            AlbumSort::Id => "albums.id",
+            AlbumSort::Title => "albums.title",
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        match self {
+            AlbumSort::Id => "INT",
+            AlbumSort::Title => "TEXT",
+        }
+    }
+
+    fn default_direction(&self) -> SortDirection {
+        SortDirection::Asc
+    }
+}
+
+impl SortField for PhotoSort {
+    const ID_COLUMN: &'static str = "photos.id";
+
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn expression(&self) -> &'static str {
+        match self {
+            PhotoSort::Id => "photos.id",
+            // Фотографии без даты съёмки - в конце списка от новых к старым
+            PhotoSort::TakenAt => "COALESCE(photos.taken_at, '-infinity')",
+            PhotoSort::Title => "COALESCE(photos.title, '')",
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        match self {
+            PhotoSort::Id => "INT",
+            PhotoSort::TakenAt => "TIMESTAMP",
+            PhotoSort::Title => "TEXT",
+        }
+    }
+
+    fn default_direction(&self) -> SortDirection {
+        match self {
+            PhotoSort::Title => SortDirection::Asc,
+            PhotoSort::Id | PhotoSort::TakenAt => SortDirection::Desc,
+        }
+    }
+}
+
+impl SortField for PersonSort {
+    const ID_COLUMN: &'static str = "persons.id";
+
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn expression(&self) -> &'static str {
+        match self {
+            PersonSort::Id => "persons.id",
+            PersonSort::Title => "persons.title",
+            PersonSort::FaceCount => {
+                "(SELECT COUNT(*) FROM faces WHERE faces.person_id = persons.id)"
+            }
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        match self {
+            PersonSort::Id => "INT",
+            PersonSort::Title => "TEXT",
+            PersonSort::FaceCount => "BIGINT",
+        }
+    }
+
+    fn default_direction(&self) -> SortDirection {
+        match self {
+            PersonSort::FaceCount => SortDirection::Desc,
+            PersonSort::Id | PersonSort::Title => SortDirection::Asc,
+        }
+    }
+}
+
+impl SortField for FaceSort {
+    const ID_COLUMN: &'static str = "faces.id";
+
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn expression(&self) -> &'static str {
+        match self {
+            FaceSort::Id => "faces.id",
+            FaceSort::PhotoId => "faces.photo_id",
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        "INT"
+    }
+
+    fn default_direction(&self) -> SortDirection {
+        SortDirection::Asc
+    }
+}
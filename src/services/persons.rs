@@ -0,0 +1,290 @@
+use diesel::dsl::sql;
+use diesel::sql_types::BigInt;
+use diesel::{
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use pgvector::Vector;
+
+use crate::db_connection::{interact, DbPool};
+use crate::errors::DbError;
+use crate::models::{
+    FaceRejection, FaceSearchResult, ListFace, ListPerson, ListPhoto, MatchedPerson, MergePersons,
+    Owner, Page, Person, PersonDetail, PersonSort, SimilarPhoto, SimilarPhotosFilters,
+    UpdatePerson,
+};
+use crate::services::facial_recognition::nearest_faces;
+use crate::services::pagination::{PageQuery, SortField};
+
+const FACE_SEARCH_LIMIT: i64 = 200;
+
+pub async fn get_persons_with_faces(
+    pool: &DbPool,
+    owner: Owner,
+    page: PageQuery<PersonSort>,
+) -> Result<Page<ListPerson>, DbError> {
+    use crate::schema::persons;
+
+    let filtered = move || {
+        let mut query = persons::table.into_boxed();
+        if let Some(user_id) = owner.user_id() {
+            query = query.filter(persons::user_id.eq(user_id));
+        }
+        query
+    };
+
+    interact(pool, move |conn| {
+        let total = filtered().count().get_result(conn)?;
+
+        let mut query = filtered()
+            .select((
+                Person::as_select(),
+                sql::<BigInt>(PersonSort::FaceCount.expression()),
+                page.key(),
+            ))
+            .order(page.order())
+            .offset(page.offset())
+            .limit(page.fetch_limit());
+        if let Some(after) = page.after() {
+            query = query.filter(after);
+        }
+
+        let rows: Vec<(Person, i64, String)> = query.load(conn)?;
+        let rows = rows
+            .into_iter()
+            .map(|(person, face_count, key)| (ListPerson::from((person, face_count)), key))
+            .collect();
+
+        Ok(page.finish(rows, total, |person| person.id))
+    })
+    .await
+}
+
+pub async fn get_person_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    person_id: i32,
+) -> Result<PersonDetail, DbError> {
+    use crate::schema::{faces, photos};
+
+    interact(pool, move |conn| {
+        let person = find_person(conn, owner, person_id)?;
+
+        let person_faces: Vec<ListFace> = faces::table
+            .filter(faces::person_id.eq(person_id))
+            .select(ListFace::as_select())
+            .order(faces::id)
+            .load(conn)?;
+
+        let person_photos: Vec<ListPhoto> = photos::table
+            .filter(
+                photos::id.eq_any(
+                    faces::table
+                        .filter(faces::person_id.eq(person_id))
+                        .select(faces::photo_id),
+                ),
+            )
+            .select(ListPhoto::as_select())
+            .order(photos::id)
+            .load(conn)?;
+
+        Ok(PersonDetail {
+            id: person.id,
+            title: person.title,
+            avatar: person.avatar,
+            faces: person_faces,
+            photos: person_photos,
+        })
+    })
+    .await
+}
+
+pub async fn update_person_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    person_id: i32,
+    changes: UpdatePerson,
+) -> Result<Person, DbError> {
+    use crate::schema::persons;
+
+    interact(pool, move |conn| {
+        let person = find_person(conn, owner, person_id)?;
+
+        diesel::update(persons::table.find(person.id))
+            .set(&changes)
+            .returning(Person::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+/// Переносит все лица `source_id` в личность `person_id` и удаляет `source_id`.
+/// Объединять можно только личности одного пользователя.
+pub async fn merge_persons(
+    pool: &DbPool,
+    owner: Owner,
+    person_id: i32,
+    merge: MergePersons,
+) -> Result<Person, DbError> {
+    use crate::schema::{face_rejections, faces, persons};
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let target = find_person(conn, owner, person_id)?;
+
+            persons::table
+                .find(merge.source_id)
+                .filter(persons::user_id.eq(target.user_id))
+                .select(persons::id)
+                .first::<i32>(conn)?;
+
+            let source_rejections: Vec<FaceRejection> = face_rejections::table
+                .filter(face_rejections::person_id.eq(merge.source_id))
+                .select(face_rejections::face_id)
+                .load::<i32>(conn)?
+                .into_iter()
+                .map(|face_id| FaceRejection {
+                    face_id,
+                    person_id: target.id,
+                })
+                .collect();
+
+            diesel::insert_into(face_rejections::table)
+                .values(&source_rejections)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            diesel::update(faces::table.filter(faces::person_id.eq(merge.source_id)))
+                .set(faces::person_id.eq(target.id))
+                .execute(conn)?;
+
+            diesel::delete(
+                face_rejections::table
+                    .filter(face_rejections::person_id.eq(target.id))
+                    .filter(
+                        face_rejections::face_id.eq_any(
+                            faces::table
+                                .filter(faces::person_id.eq(target.id))
+                                .select(faces::id),
+                        ),
+                    ),
+            )
+            .execute(conn)?;
+
+            diesel::delete(persons::table.find(merge.source_id)).execute(conn)?;
+
+            Ok(target)
+        })
+    })
+    .await
+}
+
+/// Удаляет личность, оставляя её лица без назначенной личности
+pub async fn delete_person_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    person_id: i32,
+) -> Result<(), DbError> {
+    use crate::schema::{faces, persons};
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let person = find_person(conn, owner, person_id)?;
+
+            diesel::update(faces::table.filter(faces::person_id.eq(person.id)))
+                .set(faces::person_id.eq(None::<i32>))
+                .execute(conn)?;
+
+            diesel::delete(persons::table.find(person.id)).execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+/// Личности и фотографии с лицами, похожими на `embedding`. Без
+/// `filters.max_distance` используется порог сопоставления лиц `threshold`.
+pub async fn search_by_face_embedding(
+    pool: &DbPool,
+    owner: Owner,
+    embedding: Vector,
+    filters: SimilarPhotosFilters,
+    threshold: f64,
+) -> Result<FaceSearchResult, DbError> {
+    use crate::schema::{persons, photos};
+
+    interact(pool, move |conn| {
+        let max_distance = filters.max_distance.unwrap_or(threshold);
+        let faces = nearest_faces(conn, owner, &embedding, max_distance, FACE_SEARCH_LIMIT)?;
+
+        // Лица отсортированы по расстоянию, поэтому первое вхождение - ближайшее
+        let mut person_distances: Vec<(i32, f64)> = vec![];
+        let mut photo_distances: Vec<(i32, f64)> = vec![];
+        for (face, distance) in &faces {
+            if let Some(person_id) = face.person_id {
+                if !person_distances.iter().any(|(id, _)| *id == person_id) {
+                    person_distances.push((person_id, *distance));
+                }
+            }
+            if !photo_distances.iter().any(|(id, _)| *id == face.photo_id) {
+                photo_distances.push((face.photo_id, *distance));
+            }
+        }
+        if let Some(qty) = filters.qty {
+            photo_distances.truncate(qty.max(0) as usize);
+        }
+
+        let found_persons: Vec<Person> = persons::table
+            .filter(persons::id.eq_any(person_distances.iter().map(|(id, _)| *id)))
+            .select(Person::as_select())
+            .load(conn)?;
+
+        let found_photos: Vec<ListPhoto> = photos::table
+            .filter(photos::id.eq_any(photo_distances.iter().map(|(id, _)| *id)))
+            .select(ListPhoto::as_select())
+            .load(conn)?;
+
+        Ok(FaceSearchResult {
+            persons: person_distances
+                .into_iter()
+                .filter_map(|(person_id, distance)| {
+                    let person = found_persons.iter().find(|p| p.id == person_id)?;
+                    Some(MatchedPerson {
+                        person: person.clone(),
+                        distance,
+                    })
+                })
+                .collect(),
+            photos: photo_distances
+                .into_iter()
+                .filter_map(|(photo_id, distance)| {
+                    let photo = found_photos.iter().find(|p| p.id == photo_id)?;
+                    Some(SimilarPhoto {
+                        photo: photo.clone(),
+                        distance,
+                    })
+                })
+                .collect(),
+        })
+    })
+    .await
+}
+
+/// Личность, доступная `owner`
+pub fn find_person(
+    conn: &mut PgConnection,
+    owner: Owner,
+    person_id: i32,
+) -> Result<Person, diesel::result::Error> {
+    use crate::schema::persons;
+
+    let mut query = persons::table
+        .find(person_id)
+        .select(Person::as_select())
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(persons::user_id.eq(user_id));
+    }
+
+    query.first(conn)
+}
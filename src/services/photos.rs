@@ -1,53 +1,454 @@
-use anyhow::Result;
-use axum::http::StatusCode;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use crate::db_connection::{interact, DbPool};
+use crate::errors::{DbError, PhotosSearchError};
+use crate::ml::MlBackend;
+use crate::models::{
+    ListPhoto, Owner, Page, PersonsMatch, Photo, PhotoSort, PhotosFilters, SimilarPhoto,
+    SimilarPhotosFilters,
+};
+use crate::schema::photos;
+use crate::services::pagination::PageQuery;
+use crate::storage::Storage;
+use chrono::NaiveTime;
+use diesel::{
+    dsl::sql,
+    pg::Pg,
+    sql_types::{Bool, Double},
+    BoolExpressionMethods, Connection, ExpressionMethods, PgTextExpressionMethods, QueryDsl,
+    RunQueryDsl, SelectableHelper,
+};
 use pgvector::{Vector, VectorExpressionMethods};
-use tokio::fs;
 
-use crate::db_connection::connection;
-use crate::models::{ListPhoto, Photo, PhotosFilters};
-use crate::services::facial_recognition::clip_textual_from_ml;
+const SIMILAR_PHOTOS_MAX_DISTANCE: f64 = 0.3;
 
-pub async fn get_photo_by_id(photo_id: i32) -> Result<ListPhoto> {
+pub async fn get_photo_by_id(
+    pool: &DbPool,
+    owner: Owner,
+    photo_id: i32,
+) -> Result<ListPhoto, DbError> {
     use crate::schema::photos::dsl::*;
-    Ok(photos
+
+    let mut query = photos
         .find(photo_id)
         .select(ListPhoto::as_select())
-        .first(&mut connection())?)
+        .into_boxed();
+    if let Some(owner_id) = owner.user_id() {
+        query = query.filter(user_id.eq(owner_id));
+    }
+
+    interact(pool, move |conn| query.first(conn)).await
 }
 
-pub async fn delete_photo_by_id(photo_id: i32) -> Result<(), StatusCode> {
-    use crate::schema::photos::dsl::*;
+/// Ключи всех файлов фотографии в хранилище, кроме вырезанных лиц
+pub fn photo_files(photo: Photo) -> Vec<String> {
+    let renditions = photo.renditions;
+    [
+        photo.path,
+        photo.original_path,
+        renditions.thumbnail_path,
+        renditions.preview_path,
+        renditions.thumbnail_webp_path,
+        renditions.preview_webp_path,
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
 
-    let photo: Photo = photos
-        .find(photo_id)
-        .select(Photo::as_select())
-        .first(&mut connection())
-        .unwrap();
+/// Удаляет файлы из хранилища. Ошибки только записываются в лог: запись в
+/// базе к этому моменту уже не нужна или вот-вот будет удалена.
+pub async fn delete_files(storage: &dyn Storage, files: impl IntoIterator<Item = String>) {
+    for file_path in files {
+        if let Err(err) = storage.delete(&file_path).await {
+            log::warn!("Error removing {file_path}: {err}");
+        }
+    }
+}
 
-    fs::remove_file(photo.path.unwrap())
-        .await
-        .expect("Error remove file");
+/// Удаляет фотографию. Файлы удаляются из хранилища только после удаления
+/// записи, поэтому при ошибке базы фотография остаётся целой.
+pub async fn delete_photo_by_id(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    owner: Owner,
+    photo_id: i32,
+) -> Result<(), DbError> {
+    use crate::schema::faces;
+
+    let mut query = diesel::delete(photos::table.find(photo_id)).into_boxed();
+    if let Some(owner_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(owner_id));
+    }
 
-    diesel::delete(photos.filter(id.eq(photo_id)))
-        .execute(&mut connection())
-        .expect("Error deleting photo");
+    let (photo, face_paths): (Photo, Vec<Option<String>>) = interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let face_paths = faces::table
+                .filter(faces::photo_id.eq(photo_id))
+                .select(faces::path)
+                .load(conn)?;
+            // Лица удаляются из базы каскадно вместе с изображением
+            let photo = query.returning(Photo::as_returning()).get_result(conn)?;
+            Ok((photo, face_paths))
+        })
+    })
+    .await?;
+
+    let files = photo_files(photo)
+        .into_iter()
+        .chain(face_paths.into_iter().flatten());
+    delete_files(storage, files).await;
     Ok(())
 }
 
-pub async fn get_photos_by_filters(filters: PhotosFilters) -> Vec<ListPhoto> {
+/// Экранирует `%`, `_` и `\` для точного сравнения через `ILIKE`
+fn escape_like(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, symbol| {
+        if matches!(symbol, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(symbol);
+        escaped
+    })
+}
+
+/// Фотографии `owner`, подходящие под фильтры. Текст запроса задаётся его
+/// эмбеддингом `text_embedding` и ограничивает выборку только вместе с
+/// `max_distance`.
+pub fn filtered_photos(
+    owner: Owner,
+    filters: &PhotosFilters,
+    text_embedding: Option<&Vector>,
+) -> photos::BoxedQuery<'static, Pg> {
+    use crate::schema::{album_photos, faces};
+
+    let mut query = photos::table.into_boxed();
+
+    if let Some(album_id) = filters.album_id {
+        query = query.filter(
+            photos::id.eq_any(
+                album_photos::table
+                    .select(album_photos::photo_id)
+                    .filter(album_photos::album_id.eq(album_id)),
+            ),
+        );
+    }
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(user_id));
+    }
+
+    let person_ids = filters.all_person_ids();
+    if !person_ids.is_empty() {
+        match filters.persons_match.unwrap_or_default() {
+            PersonsMatch::Or => {
+                query = query.filter(
+                    photos::id.eq_any(
+                        faces::table
+                            .select(faces::photo_id)
+                            .filter(faces::person_id.eq_any(person_ids)),
+                    ),
+                );
+            }
+            PersonsMatch::And => {
+                for person_id in person_ids {
+                    query = query.filter(
+                        photos::id.eq_any(
+                            faces::table
+                                .select(faces::photo_id)
+                                .filter(faces::person_id.eq(person_id)),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(taken_from) = filters.taken_from {
+        query = query.filter(photos::taken_at.ge(taken_from.and_time(NaiveTime::MIN)));
+    }
+    if let Some(taken_to) = filters.taken_to.and_then(|date| date.succ_opt()) {
+        query = query.filter(photos::taken_at.lt(taken_to.and_time(NaiveTime::MIN)));
+    }
+    if let Some(camera_make) = &filters.camera_make {
+        query = query.filter(photos::camera_make.ilike(escape_like(camera_make)));
+    }
+    if let Some(camera_model) = &filters.camera_model {
+        query = query.filter(photos::camera_model.ilike(escape_like(camera_model)));
+    }
+    if let Some(lens_model) = &filters.lens_model {
+        query = query.filter(photos::lens_model.ilike(escape_like(lens_model)));
+    }
+    if let Some(iso_min) = filters.iso_min {
+        query = query.filter(photos::iso.ge(iso_min));
+    }
+    if let Some(iso_max) = filters.iso_max {
+        query = query.filter(photos::iso.le(iso_max));
+    }
+    match filters.has_location {
+        Some(true) => {
+            query = query
+                .filter(photos::latitude.is_not_null())
+                .filter(photos::longitude.is_not_null());
+        }
+        Some(false) => {
+            query = query.filter(photos::latitude.is_null().or(photos::longitude.is_null()));
+        }
+        None => {}
+    }
+    if let (Some(latitude), Some(longitude), Some(radius_km)) =
+        (filters.latitude, filters.longitude, filters.radius_km)
+    {
+        // Расстояние по большому кругу, формула гаверсинусов
+        query = query.filter(
+            sql::<Bool>("2 * 6371 * asin(sqrt(power(sin(radians(photos.latitude - ")
+                .bind::<Double, _>(latitude)
+                .sql(") / 2), 2) + cos(radians(")
+                .bind::<Double, _>(latitude)
+                .sql(")) * cos(radians(photos.latitude)) * power(sin(radians(photos.longitude - ")
+                .bind::<Double, _>(longitude)
+                .sql(") / 2), 2))) <= ")
+                .bind::<Double, _>(radius_km),
+        );
+    }
+    if let (Some(embedding), Some(max_distance)) = (text_embedding, filters.max_distance) {
+        query = query.filter(
+            photos::embedding
+                .cosine_distance(embedding.clone())
+                .le(max_distance),
+        );
+    }
+
+    query
+}
+
+pub async fn get_photos_by_filters(
+    pool: &DbPool,
+    ml: &dyn MlBackend,
+    owner: Owner,
+    filters: PhotosFilters,
+    page: PageQuery<PhotoSort>,
+) -> Result<Page<ListPhoto>, PhotosSearchError> {
+    let text_embedding = match &filters.text {
+        Some(text) => Some(Vector::from(ml.clip_textual(text).await?)),
+        None => None,
+    };
+
+    Ok(interact(pool, move |conn| {
+        let total = filtered_photos(owner, &filters, text_embedding.as_ref())
+            .count()
+            .get_result(conn)?;
+
+        let mut query = filtered_photos(owner, &filters, text_embedding.as_ref())
+            .select((ListPhoto::as_select(), page.key()))
+            .offset(page.offset())
+            .limit(page.fetch_limit());
+        query = match text_embedding {
+            Some(pg_vector_embedding) => query
+                .order(photos::embedding.cosine_distance(pg_vector_embedding))
+                .then_order_by(page.order()),
+            None => query.order(page.order()),
+        };
+        if let Some(after) = page.after() {
+            query = query.filter(after);
+        }
+
+        Ok(page.finish(query.load(conn)?, total, |photo| photo.id))
+    })
+    .await?)
+}
+
+pub async fn get_photo_embedding(
+    pool: &DbPool,
+    owner: Owner,
+    photo_id: i32,
+) -> Result<Option<Vector>, DbError> {
+    use crate::schema::photos;
+
+    let mut query = photos::table
+        .find(photo_id)
+        .select(photos::embedding)
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(user_id));
+    }
+
+    interact(pool, move |conn| query.first(conn)).await
+}
+
+/// Фотографии, упорядоченные по близости CLIP эмбеддинга к `embedding`
+pub async fn get_similar_photos(
+    pool: &DbPool,
+    owner: Owner,
+    embedding: Vector,
+    exclude_photo_id: Option<i32>,
+    filters: SimilarPhotosFilters,
+) -> Result<Vec<SimilarPhoto>, DbError> {
     use crate::schema::photos;
 
-    let mut query = photos::table.select(ListPhoto::as_select()).into_boxed();
+    let max_distance = filters.max_distance.unwrap_or(SIMILAR_PHOTOS_MAX_DISTANCE);
+
+    let mut query = photos::table
+        .select((
+            ListPhoto::as_select(),
+            photos::embedding.cosine_distance(embedding.clone()),
+        ))
+        .filter(photos::embedding.is_not_null())
+        .filter(
+            photos::embedding
+                .cosine_distance(embedding.clone())
+                .le(max_distance),
+        )
+        .order(photos::embedding.cosine_distance(embedding))
+        .into_boxed();
+
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(user_id));
+    }
 
-    if let Some(text) = filters.text {
-        let pg_vector_embedding = Vector::from(clip_textual_from_ml(text).await.unwrap());
-        query = query.order(photos::embedding.cosine_distance(pg_vector_embedding));
+    if let Some(photo_id) = exclude_photo_id {
+        query = query.filter(photos::id.ne(photo_id));
     }
 
     if let Some(qty) = filters.qty {
         query = query.limit(qty.into());
     }
 
-    query.load(&mut connection()).unwrap()
+    let rows: Vec<(ListPhoto, Option<f64>)> = interact(pool, move |conn| query.load(conn)).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(photo, distance)| SimilarPhoto {
+            photo,
+            distance: distance.unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::{FakeMlBackend, RecognizedFaceOutput};
+    use crate::models::PageParams;
+    use crate::schema::faces;
+    use crate::test_utils::{test_image, TestEnv};
+
+    async fn search(
+        env: &TestEnv,
+        ml: &FakeMlBackend,
+        user_id: i32,
+        filters: PhotosFilters,
+    ) -> Vec<i32> {
+        let page = PageQuery::new(&PageParams::default(), filters.page_sort()).unwrap();
+        get_photos_by_filters(&env.pool, ml, Owner::User(user_id), filters, page)
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|photo| photo.id)
+            .collect()
+    }
+
+    fn cosine_distance(a: &[f32], b: &[f32]) -> f64 {
+        let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| *x as f64 * *x as f64).sum::<f64>().sqrt();
+        1.0 - dot / (norm(a) * norm(b))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn text_search_orders_photos_by_embedding_distance() {
+        let env = TestEnv::new();
+        let user_id = env.create_user().await.id;
+        let ml = FakeMlBackend::new();
+        for color in [[200, 0, 0], [0, 200, 0], [0, 0, 200], [200, 200, 0]] {
+            env.upload(&ml, user_id, test_image(color)).await;
+        }
+
+        let found = search(
+            &env,
+            &ml,
+            user_id,
+            PhotosFilters {
+                text: Some("кот на диване".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let embeddings: Vec<(i32, Option<Vector>)> = interact(&env.pool, move |conn| {
+            photos::table
+                .filter(photos::user_id.eq(user_id))
+                .select((photos::id, photos::embedding))
+                .load(conn)
+        })
+        .await
+        .unwrap();
+        let text_embedding = FakeMlBackend::embedding(("text", "кот на диване"));
+        let mut expected: Vec<(f64, i32)> = embeddings
+            .into_iter()
+            .map(|(id, embedding)| {
+                let embedding = embedding.expect("processed photo has an embedding");
+                (cosine_distance(&text_embedding, embedding.as_slice()), id)
+            })
+            .collect();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        assert_eq!(found.len(), 4);
+        assert_eq!(
+            found,
+            expected.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn person_filter_returns_photos_with_the_person() {
+        let env = TestEnv::new();
+        let user_id = env.create_user().await.id;
+        let face = RecognizedFaceOutput {
+            score: 1.0,
+            bbox: [16.0, 16.0, 48.0, 48.0],
+            landmarks: [(32.0, 32.0); 5],
+            embedding: FakeMlBackend::embedding("face"),
+        };
+        let with_face = FakeMlBackend::with_faces(vec![face]);
+        let without_faces = FakeMlBackend::with_faces(vec![]);
+
+        let first = env
+            .upload(&with_face, user_id, test_image([200, 0, 0]))
+            .await;
+        let second = env
+            .upload(&with_face, user_id, test_image([0, 200, 0]))
+            .await;
+        env.upload(&without_faces, user_id, test_image([0, 0, 200]))
+            .await;
+
+        let person_ids: Vec<Option<i32>> = interact(&env.pool, move |conn| {
+            faces::table
+                .filter(faces::photo_id.eq_any([first, second]))
+                .order(faces::photo_id)
+                .select(faces::person_id)
+                .load(conn)
+        })
+        .await
+        .unwrap();
+        // Одинаковые эмбеддинги лиц относятся к одной личности
+        assert_eq!(person_ids.len(), 2);
+        assert_eq!(person_ids[0], person_ids[1]);
+        let person_id = person_ids[0].expect("face is assigned to a person");
+
+        let mut found = search(
+            &env,
+            &with_face,
+            user_id,
+            PhotosFilters {
+                person_id: Some(person_id),
+                ..Default::default()
+            },
+        )
+        .await;
+        found.sort_unstable();
+        assert_eq!(found, vec![first, second]);
+
+        let all = search(&env, &with_face, user_id, PhotosFilters::default()).await;
+        assert_eq!(all.len(), 3);
+    }
 }
@@ -0,0 +1,427 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl,
+    RunQueryDsl, SelectableHelper,
+};
+use rand::RngCore;
+
+use crate::{
+    db_connection::{interact, interact_with, DbPool},
+    errors::{DbError, ShareLinkError},
+    middleware::authorize::{hash_password, verify_password},
+    models::{ListPhoto, NewShareLink, Owner, Photo, ShareLink, SharedResource},
+    services::albums::find_album,
+    storage::Storage,
+};
+
+/// Случайных байт в токене ссылки
+const TOKEN_BYTES: usize = 24;
+
+/// Исходный файл фотографии, открытой по ссылке
+pub struct SharedOriginal {
+    pub content: Vec<u8>,
+    pub mime_type: String,
+    pub file_name: String,
+}
+
+/// Создаёт публичную ссылку на альбом или фотографию `owner`
+pub async fn create_share_link(
+    pool: &DbPool,
+    owner: Owner,
+    uid: i32,
+    new_link: NewShareLink,
+) -> Result<ShareLink, ShareLinkError> {
+    use crate::schema::{photos, share_links};
+
+    if new_link.album_id.is_some() == new_link.photo_id.is_some() {
+        return Err(ShareLinkError::InvalidTarget);
+    }
+    if new_link
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(ShareLinkError::InvalidExpiry);
+    }
+
+    // bcrypt нагружает процессор, поэтому выполняется вне исполнителя
+    let password = match new_link.password {
+        Some(password) => {
+            Some(tokio::task::spawn_blocking(move || hash_password(&password)).await??)
+        }
+        None => None,
+    };
+
+    let mut token = [0u8; TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut token);
+    let token = URL_SAFE_NO_PAD.encode(token);
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            if let Some(album_id) = new_link.album_id {
+                find_album(conn, owner, album_id)?;
+            }
+            if let Some(photo_id) = new_link.photo_id {
+                let mut query = photos::table.find(photo_id).select(photos::id).into_boxed();
+                if let Some(user_id) = owner.user_id() {
+                    query = query.filter(photos::user_id.eq(user_id));
+                }
+                query.first::<i32>(conn)?;
+            }
+
+            Ok(diesel::insert_into(share_links::table)
+                .values((
+                    share_links::token.eq(token),
+                    share_links::user_id.eq(uid),
+                    share_links::album_id.eq(new_link.album_id),
+                    share_links::photo_id.eq(new_link.photo_id),
+                    share_links::password.eq(password),
+                    share_links::allow_download.eq(new_link.allow_download),
+                    share_links::expires_at.eq(new_link.expires_at),
+                ))
+                .returning(ShareLink::as_returning())
+                .get_result(conn)?)
+        })
+    })
+    .await
+}
+
+pub async fn get_share_links(pool: &DbPool, owner: Owner) -> Result<Vec<ShareLink>, DbError> {
+    use crate::schema::share_links;
+
+    let mut query = share_links::table
+        .select(ShareLink::as_select())
+        .order(share_links::id.desc())
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(share_links::user_id.eq(user_id));
+    }
+
+    interact(pool, move |conn| query.load(conn)).await
+}
+
+pub async fn delete_share_link(pool: &DbPool, owner: Owner, link_id: i32) -> Result<(), DbError> {
+    use crate::schema::share_links;
+
+    let mut query = diesel::delete(share_links::table.find(link_id)).into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(share_links::user_id.eq(user_id));
+    }
+
+    interact(pool, move |conn| match query.execute(conn)? {
+        0 => Err(diesel::result::Error::NotFound),
+        _ => Ok(()),
+    })
+    .await
+}
+
+/// Неудачных попыток пароля до первой блокировки ссылки
+const FREE_PASSWORD_ATTEMPTS: i32 = 5;
+
+/// Блокировка после первой лишней попытки, дальше удваивается
+const BASE_LOCKOUT_SECS: i64 = 30;
+
+/// Наибольшая блокировка, 30 секунд * 2^5 = 16 минут
+const MAX_LOCKOUT_DOUBLINGS: i32 = 5;
+
+/// Действующая ссылка по токену. Для защищённой ссылки нужен её пароль,
+/// после нескольких неверных паролей подряд ссылка на время блокируется.
+pub async fn open_share_link(
+    pool: &DbPool,
+    token: String,
+    password: Option<String>,
+) -> Result<ShareLink, ShareLinkError> {
+    use crate::schema::share_links;
+
+    let (link, password_hash, locked_until) = find_share_link(pool, token).await?;
+    let Some(password_hash) = password_hash else {
+        return Ok(link);
+    };
+    check_lockout(locked_until, Utc::now())?;
+
+    let password = password.ok_or(ShareLinkError::PasswordRequired)?;
+    let is_valid =
+        tokio::task::spawn_blocking(move || verify_password(&password, &password_hash)).await??;
+
+    let link_id = link.id;
+    if !is_valid {
+        interact(pool, move |conn| {
+            let failed_attempts: i32 = diesel::update(share_links::table.find(link_id))
+                .set(share_links::failed_attempts.eq(share_links::failed_attempts + 1))
+                .returning(share_links::failed_attempts)
+                .get_result(conn)?;
+            if let Some(lockout) = password_lockout(failed_attempts) {
+                diesel::update(share_links::table.find(link_id))
+                    .set(share_links::locked_until.eq(Utc::now() + lockout))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+        .await?;
+        return Err(ShareLinkError::WrongPassword);
+    }
+
+    interact(pool, move |conn| {
+        diesel::update(share_links::table.find(link_id))
+            .filter(share_links::failed_attempts.gt(0))
+            .set((
+                share_links::failed_attempts.eq(0),
+                share_links::locked_until.eq(None::<DateTime<Utc>>),
+            ))
+            .execute(conn)
+    })
+    .await?;
+
+    Ok(link)
+}
+
+/// Действующая ссылка по токену без проверки пароля, для подписанных
+/// адресов файлов, выданных после ввода пароля
+pub async fn open_signed_share_link(
+    pool: &DbPool,
+    token: String,
+) -> Result<ShareLink, ShareLinkError> {
+    let (link, _, _) = find_share_link(pool, token).await?;
+    Ok(link)
+}
+
+/// Ссылка с хэшем пароля и блокировкой, истёкшая ссылка не открывается
+async fn find_share_link(
+    pool: &DbPool,
+    token: String,
+) -> Result<(ShareLink, Option<String>, Option<DateTime<Utc>>), ShareLinkError> {
+    use crate::schema::share_links;
+
+    let found: (ShareLink, Option<String>, Option<DateTime<Utc>>) = interact(pool, move |conn| {
+        share_links::table
+            .filter(share_links::token.eq(token))
+            .select((
+                ShareLink::as_select(),
+                share_links::password,
+                share_links::locked_until,
+            ))
+            .first(conn)
+    })
+    .await?;
+
+    check_expiry(found.0.expires_at, Utc::now())?;
+    Ok(found)
+}
+
+fn check_expiry(
+    expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), ShareLinkError> {
+    match expires_at {
+        Some(expires_at) if expires_at <= now => Err(ShareLinkError::Expired),
+        _ => Ok(()),
+    }
+}
+
+fn check_lockout(
+    locked_until: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), ShareLinkError> {
+    match locked_until {
+        Some(locked_until) if locked_until > now => Err(ShareLinkError::TooManyAttempts),
+        _ => Ok(()),
+    }
+}
+
+/// На сколько блокируется ссылка после `failed_attempts` неверных паролей
+fn password_lockout(failed_attempts: i32) -> Option<Duration> {
+    let extra_attempts = failed_attempts - FREE_PASSWORD_ATTEMPTS;
+    if extra_attempts <= 0 {
+        return None;
+    }
+    let doublings = (extra_attempts - 1).min(MAX_LOCKOUT_DOUBLINGS);
+    Some(Duration::seconds(BASE_LOCKOUT_SECS << doublings))
+}
+
+/// Альбом или фотография ссылки, каждое открытие увеличивает счётчик
+/// просмотров
+pub async fn view_share_link(pool: &DbPool, link: ShareLink) -> Result<SharedResource, DbError> {
+    use crate::schema::{photos, share_links};
+
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            diesel::update(share_links::table.find(link.id))
+                .set(share_links::view_count.eq(share_links::view_count + 1))
+                .execute(conn)?;
+
+            let album = match link.album_id {
+                Some(album_id) => Some(find_album(conn, Owner::Any, album_id)?),
+                None => None,
+            };
+            let photo = match link.photo_id {
+                Some(photo_id) => Some(
+                    photos::table
+                        .find(photo_id)
+                        .select(ListPhoto::as_select())
+                        .first(conn)?,
+                ),
+                None => None,
+            };
+
+            Ok(SharedResource {
+                album,
+                photo,
+                allow_download: link.allow_download,
+                expires_at: link.expires_at,
+            })
+        })
+    })
+    .await
+}
+
+/// Фотография, открытая по ссылке напрямую или через альбом
+pub async fn get_shared_photo(
+    pool: &DbPool,
+    link: &ShareLink,
+    photo_id: i32,
+) -> Result<Photo, DbError> {
+    use crate::schema::{album_photos, photos};
+
+    let (link_photo_id, link_album_id) = (link.photo_id, link.album_id);
+    interact(pool, move |conn| {
+        photos::table
+            .find(photo_id)
+            .filter(
+                photos::id
+                    .nullable()
+                    .eq(link_photo_id)
+                    .or(photos::id.eq_any(
+                        album_photos::table
+                            .select(album_photos::photo_id)
+                            .filter(album_photos::album_id.nullable().eq(link_album_id)),
+                    )),
+            )
+            .select(Photo::as_select())
+            .first(conn)
+    })
+    .await
+}
+
+/// Исходный файл фотографии ссылки для скачивания
+pub async fn get_shared_original(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    link: &ShareLink,
+    photo_id: i32,
+) -> Result<SharedOriginal, ShareLinkError> {
+    if !link.allow_download {
+        return Err(ShareLinkError::DownloadForbidden);
+    }
+
+    let photo = get_shared_photo(pool, link, photo_id).await?;
+
+    // Изображения, загруженные до сохранения исходников, отдаются в JPEG
+    let (file_path, mime_type) = match (photo.original_path, photo.path) {
+        (Some(original_path), _) => (original_path, photo.mime_type),
+        (None, Some(path)) => (path, Some("image/jpeg".to_string())),
+        (None, None) => return Err(diesel::result::Error::NotFound.into()),
+    };
+    let file_name = match file_path.rsplit_once('.') {
+        Some((_, extension)) => format!("{photo_id}.{extension}"),
+        None => photo_id.to_string(),
+    };
+
+    Ok(SharedOriginal {
+        content: storage.get(&file_path).await?,
+        mime_type: mime_type.unwrap_or_else(|| "application/octet-stream".to_string()),
+        file_name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::FakeMlBackend;
+    use crate::test_utils::{test_image, TestEnv};
+
+    #[test]
+    fn expired_link_is_rejected() {
+        let now = Utc::now();
+        assert!(check_expiry(None, now).is_ok());
+        assert!(check_expiry(Some(now + Duration::seconds(1)), now).is_ok());
+        assert!(matches!(
+            check_expiry(Some(now), now),
+            Err(ShareLinkError::Expired)
+        ));
+        assert!(matches!(
+            check_expiry(Some(now - Duration::days(1)), now),
+            Err(ShareLinkError::Expired)
+        ));
+    }
+
+    #[test]
+    fn locked_link_is_rejected_until_lock_ends() {
+        let now = Utc::now();
+        assert!(check_lockout(None, now).is_ok());
+        assert!(check_lockout(Some(now), now).is_ok());
+        assert!(matches!(
+            check_lockout(Some(now + Duration::seconds(1)), now),
+            Err(ShareLinkError::TooManyAttempts)
+        ));
+    }
+
+    #[test]
+    fn lockout_doubles_after_free_attempts() {
+        for attempts in 0..=FREE_PASSWORD_ATTEMPTS {
+            assert_eq!(password_lockout(attempts), None);
+        }
+        let lockout = |extra| password_lockout(FREE_PASSWORD_ATTEMPTS + extra);
+        assert_eq!(lockout(1), Some(Duration::seconds(30)));
+        assert_eq!(lockout(2), Some(Duration::seconds(60)));
+        assert_eq!(lockout(6), Some(Duration::minutes(16)));
+        assert_eq!(lockout(100), Some(Duration::minutes(16)));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn protected_link_needs_password_and_locks_after_wrong_ones() {
+        let env = TestEnv::new();
+        let ml = FakeMlBackend::new();
+        let user_id = env.create_user().await.id;
+        let photo_id = env.upload(&ml, user_id, test_image([40, 80, 120])).await;
+        let link = create_share_link(
+            &env.pool,
+            Owner::User(user_id),
+            user_id,
+            NewShareLink {
+                album_id: None,
+                photo_id: Some(photo_id),
+                password: Some("secret".to_string()),
+                allow_download: false,
+                expires_at: None,
+            },
+        )
+        .await
+        .unwrap();
+        let open = |password: Option<&str>| {
+            open_share_link(&env.pool, link.token.clone(), password.map(str::to_string))
+        };
+
+        assert!(matches!(
+            open(None).await,
+            Err(ShareLinkError::PasswordRequired)
+        ));
+        assert!(open(Some("secret")).await.is_ok());
+
+        for _ in 0..=FREE_PASSWORD_ATTEMPTS {
+            assert!(matches!(
+                open(Some("wrong")).await,
+                Err(ShareLinkError::WrongPassword)
+            ));
+        }
+        // Во время блокировки не принимается даже верный пароль
+        assert!(matches!(
+            open(Some("secret")).await,
+            Err(ShareLinkError::TooManyAttempts)
+        ));
+        // Подписанные адреса файлов блокировка не затрагивает
+        assert!(open_signed_share_link(&env.pool, link.token.clone())
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime};
+use diesel::{
+    dsl::{count_star, sql},
+    sql_types::{Nullable, Timestamp},
+    ExpressionMethods, PgSortExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::db_connection::{interact, DbPool};
+use crate::errors::DbError;
+use crate::models::{ListPhoto, Owner, TimelineBucket, TimelineGranularity, TimelinePhotosQuery};
+
+const TIMELINE_DEFAULT_LIMIT: i64 = 100;
+const TIMELINE_MAX_LIMIT: i64 = 500;
+
+/// Количество фотографий по периодам съёмки, от новых к старым. Фотографии
+/// без даты съёмки собраны в последний период с пустой датой.
+pub async fn get_timeline(
+    pool: &DbPool,
+    owner: Owner,
+    granularity: TimelineGranularity,
+) -> Result<Vec<TimelineBucket>, DbError> {
+    use crate::schema::photos;
+
+    // Единица подставляется в текст запроса, а не параметром: иначе Postgres
+    // не сочтёт выражения в SELECT и GROUP BY одинаковыми
+    let bucket = || {
+        sql::<Nullable<Timestamp>>(&format!(
+            "date_trunc('{}', photos.taken_at)",
+            granularity.as_str()
+        ))
+    };
+
+    let mut query = photos::table
+        .group_by(bucket())
+        .select((bucket(), count_star()))
+        .order(bucket().desc().nulls_last())
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(user_id));
+    }
+
+    let rows: Vec<(Option<NaiveDateTime>, i64)> =
+        interact(pool, move |conn| query.load(conn)).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(date, count)| TimelineBucket {
+            date: date.map(|date| date.date()),
+            count,
+        })
+        .collect())
+}
+
+/// Фотографии одного периода съёмки, от новых к старым
+pub async fn get_timeline_photos(
+    pool: &DbPool,
+    owner: Owner,
+    params: TimelinePhotosQuery,
+) -> Result<Vec<ListPhoto>, DbError> {
+    use crate::schema::photos;
+
+    let mut query = photos::table
+        .select(ListPhoto::as_select())
+        .order((photos::taken_at.desc(), photos::id.desc()))
+        .offset(params.offset.unwrap_or(0).max(0))
+        .limit(
+            params
+                .limit
+                .unwrap_or(TIMELINE_DEFAULT_LIMIT)
+                .clamp(1, TIMELINE_MAX_LIMIT),
+        )
+        .into_boxed();
+    if let Some(user_id) = owner.user_id() {
+        query = query.filter(photos::user_id.eq(user_id));
+    }
+
+    match params.date {
+        Some(date) => {
+            let (start, end) = bucket_range(date, params.granularity.unwrap_or_default());
+            query = query
+                .filter(photos::taken_at.ge(start.and_time(NaiveTime::MIN)))
+                .filter(photos::taken_at.lt(end.and_time(NaiveTime::MIN)));
+        }
+        None => {
+            query = query.filter(photos::taken_at.is_null());
+        }
+    }
+
+    interact(pool, move |conn| query.load(conn)).await
+}
+
+/// Первый день периода, содержащего `date`, и первый день следующего
+fn bucket_range(date: NaiveDate, granularity: TimelineGranularity) -> (NaiveDate, NaiveDate) {
+    let start = match granularity {
+        TimelineGranularity::Year => date.with_ordinal(1),
+        TimelineGranularity::Month => date.with_day(1),
+        TimelineGranularity::Day => Some(date),
+    }
+    .unwrap_or(date);
+    let end = match granularity {
+        TimelineGranularity::Year => start.checked_add_months(Months::new(12)),
+        TimelineGranularity::Month => start.checked_add_months(Months::new(1)),
+        TimelineGranularity::Day => start.succ_opt(),
+    };
+
+    (start, end.unwrap_or(NaiveDate::MAX))
+}
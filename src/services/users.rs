@@ -1,54 +1,438 @@
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+use std::io::Cursor;
 
+use chrono::Utc;
+use diesel::{
+    result::DatabaseErrorKind, Connection, ExpressionMethods, OptionalExtension,
+    PgTextExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+use image::{io::Reader as ImageReader, ImageError};
+
+use crate::config::{AuthConfig, Config};
+use crate::db_connection::{interact, interact_with, DbPool};
+use crate::errors::{DbError, UserError};
 use crate::models::UsersQuery;
-use crate::{db_connection::connection, middleware::authorize::hash_password, models::*};
+use crate::services::auth::{random_token, revoke_user_sessions};
+use crate::services::exif::read_exif;
+use crate::services::images::{apply_orientation, encode_avatar, flatten_to_rgb};
+use crate::services::pagination::PageQuery;
+use crate::services::photos::{delete_files, photo_files};
+use crate::storage::Storage;
+use crate::{
+    middleware::authorize::{hash_password, verify_password},
+    models::*,
+};
 
-pub async fn get_user_by_email(user_email: &str) -> Option<User> {
+pub async fn get_user_by_email(pool: &DbPool, user_email: &str) -> Result<Option<User>, DbError> {
     use crate::schema::users::dsl::*;
 
-    match users
-        .filter(email.eq(user_email))
-        .select(User::as_select())
-        .first(&mut connection())
-    {
-        Ok(user) => Some(user),
-        _ => None,
+    let user_email = user_email.to_string();
+    interact(pool, move |conn| {
+        users
+            .filter(email.eq(user_email))
+            .select(User::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+}
+
+pub async fn get_users_with_filters(
+    pool: &DbPool,
+    params: UsersQuery,
+    page: PageQuery<UserSort>,
+) -> Result<Page<User>, DbError> {
+    use crate::schema::users;
+
+    let filtered = move || {
+        let mut query = users::table.into_boxed();
+        if let Some(email) = &params.email {
+            query = query.filter(users::email.ilike(format!("%{email}%")));
+        }
+        if let Some(username) = &params.username {
+            query = query.filter(users::username.ilike(format!("%{username}%")));
+        }
+        query
+    };
+
+    interact(pool, move |conn| {
+        let total = filtered().count().get_result(conn)?;
+
+        let mut query = filtered()
+            .select((User::as_select(), page.key()))
+            .order(page.order())
+            .offset(page.offset())
+            .limit(page.fetch_limit());
+        if let Some(after) = page.after() {
+            query = query.filter(after);
+        }
+
+        Ok(page.finish(query.load(conn)?, total, |user| user.id))
+    })
+    .await
+}
+
+pub async fn get_user_by_id(pool: &DbPool, user_id: i32) -> Result<User, DbError> {
+    interact(pool, move |conn| {
+        crate::schema::users::dsl::users
+            .find(user_id)
+            .select(User::as_select())
+            .first(conn)
+    })
+    .await
+}
+
+/// Удаляет пользователя вместе с его фотографиями и файлами. Альбомы,
+/// личности, ссылки, ключи и сессии удаляются из базы каскадно.
+pub async fn delete_user_by_id(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    user_id: i32,
+) -> Result<(), DbError> {
+    use crate::schema::{faces, photos, users};
+
+    let files = interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            let avatar: Option<String> = users::table
+                .find(user_id)
+                .select(users::avatar)
+                .first(conn)?;
+            let user_photos: Vec<Photo> = photos::table
+                .filter(photos::user_id.eq(user_id))
+                .select(Photo::as_select())
+                .load(conn)?;
+            let face_paths: Vec<Option<String>> = faces::table
+                .filter(
+                    faces::photo_id.eq_any(
+                        photos::table
+                            .select(photos::id)
+                            .filter(photos::user_id.eq(user_id)),
+                    ),
+                )
+                .select(faces::path)
+                .load(conn)?;
+
+            // Лица, задания и места в альбомах удаляются каскадно с фотографиями
+            diesel::delete(photos::table.filter(photos::user_id.eq(user_id))).execute(conn)?;
+            diesel::delete(users::table.find(user_id)).execute(conn)?;
+
+            let files: Vec<String> = user_photos
+                .into_iter()
+                .flat_map(photo_files)
+                .chain(face_paths.into_iter().flatten())
+                .chain(avatar)
+                .collect();
+            Ok(files)
+        })
+    })
+    .await?;
+
+    // Файлы удаляются после фиксации транзакции, чтобы откат не оставил
+    // записей без файлов
+    delete_files(storage, files).await;
+    Ok(())
+}
+
+pub async fn create_user(pool: &DbPool, mut new_user: NewUser) -> Result<User, DbError> {
+    interact(pool, move |conn| {
+        // bcrypt нагружает процессор, поэтому тоже выполняется вне исполнителя
+        new_user.password = hash_password(&new_user.password).unwrap();
+
+        diesel::insert_into(crate::schema::users::table)
+            .values(&new_user)
+            .returning(User::as_returning())
+            .get_result(conn)
+    })
+    .await
+}
+
+/// Наименьшая длина пароля, задаваемого самим пользователем
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Наибольшая длина имени и почты, как в колонках `users`
+const MAX_NAME_LEN: usize = 50;
+
+fn valid_username(username: &str) -> Result<String, UserError> {
+    let username = username.trim();
+    match username.is_empty() || username.chars().count() > MAX_NAME_LEN {
+        true => Err(UserError::InvalidUsername),
+        false => Ok(username.to_string()),
     }
 }
 
-pub async fn get_users_with_filters(_params: UsersQuery) -> Vec<User> {
-    use crate::schema::users::dsl::*;
+fn valid_email(email: &str) -> Result<String, UserError> {
+    let email = email.trim();
+    let is_valid = email.chars().count() <= MAX_NAME_LEN
+        && email
+            .split_once('@')
+            .is_some_and(|(name, domain)| !name.is_empty() && domain.contains('.'));
+    match is_valid {
+        true => Ok(email.to_string()),
+        false => Err(UserError::InvalidEmail),
+    }
+}
 
-    users
-        // .filter(email.like(params.email))
-        // .filter(username.like(params.username))
-        .limit(5)
-        .select(User::as_select())
-        .load(&mut connection())
-        .expect("Error loading posts")
+fn valid_password(password: &str) -> Result<(), UserError> {
+    match password.chars().count() < MIN_PASSWORD_LEN {
+        true => Err(UserError::WeakPassword),
+        false => Ok(()),
+    }
 }
 
-pub async fn get_user_by_id(user_id: i32) -> Result<User, diesel::result::Error> {
-    crate::schema::users::dsl::users
-        .find(user_id)
-        .select(User::as_select())
-        .first(&mut connection())
+/// Нарушение уникальности имени или почты превращается в понятную ошибку
+fn unique_violation(err: diesel::result::Error) -> UserError {
+    match &err {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+            match info.constraint_name() {
+                Some("users_username_key") => UserError::UsernameTaken,
+                Some("users_email_key") => UserError::EmailTaken,
+                _ => err.into(),
+            }
+        }
+        _ => err.into(),
+    }
 }
 
-pub async fn delete_user_by_id(user_id: i32) {
-    use crate::schema::users::dsl::*;
+/// Меняет профиль пользователя, незаданные поля остаются прежними
+pub async fn update_profile(
+    pool: &DbPool,
+    user_id: i32,
+    profile: UpdateProfile,
+) -> Result<User, UserError> {
+    use crate::schema::users;
+
+    let username = profile
+        .username
+        .as_deref()
+        .map(valid_username)
+        .transpose()?;
+
+    interact_with(pool, move |conn| {
+        let user = match username {
+            Some(username) => diesel::update(users::table.find(user_id))
+                .set(users::username.eq(username))
+                .returning(User::as_returning())
+                .get_result(conn)
+                .map_err(unique_violation)?,
+            None => users::table
+                .find(user_id)
+                .select(User::as_select())
+                .first(conn)?,
+        };
+        Ok(user)
+    })
+    .await
+}
+
+/// Меняет пароль после проверки текущего. Остальные сессии пользователя
+/// отзываются, продолжает работать только `session_id`.
+pub async fn change_password(
+    pool: &DbPool,
+    user: User,
+    session_id: Option<String>,
+    change: ChangePassword,
+) -> Result<(), UserError> {
+    use crate::schema::users;
+
+    valid_password(&change.new_password)?;
+
+    // bcrypt нагружает процессор, поэтому выполняется вне исполнителя
+    let password_hash = tokio::task::spawn_blocking(move || {
+        match verify_password(&change.current_password, &user.password)? {
+            true => Ok(hash_password(&change.new_password)?),
+            false => Err(UserError::WrongPassword),
+        }
+    })
+    .await??;
+
+    let user_id = user.id;
+    interact(pool, move |conn| {
+        conn.transaction(|conn| {
+            diesel::update(users::table.find(user_id))
+                .set(users::password.eq(password_hash))
+                .execute(conn)?;
+            revoke_user_sessions(conn, user_id, session_id.as_deref())
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Сохраняет аватар пользователя из загруженного изображения, заменяя прежний
+pub async fn set_avatar(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    config: &Config,
+    user_id: i32,
+    file_content: Vec<u8>,
+) -> Result<User, UserError> {
+    use crate::schema::users;
+
+    let renditions = config.renditions.clone();
+    let content = tokio::task::spawn_blocking(move || -> Result<_, UserError> {
+        let orientation = read_exif(&file_content).orientation;
+        let image = ImageReader::new(Cursor::new(file_content))
+            .with_guessed_format()
+            .map_err(ImageError::IoError)?
+            .decode()?;
+        let image = flatten_to_rgb(&apply_orientation(image, orientation));
+        Ok(encode_avatar(&image, &renditions)?)
+    })
+    .await??;
+
+    let key = format!("{}/{user_id}.jpeg", config.storage.avatars_dir);
+    storage.put(&key, content, "image/jpeg").await?;
+
+    Ok(interact(pool, move |conn| {
+        diesel::update(users::table.find(user_id))
+            .set(users::avatar.eq(key))
+            .returning(User::as_returning())
+            .get_result(conn)
+    })
+    .await?)
+}
+
+/// Удаляет аватар пользователя вместе с файлом
+pub async fn delete_avatar(
+    pool: &DbPool,
+    storage: &dyn Storage,
+    user_id: i32,
+) -> Result<User, UserError> {
+    use crate::schema::users;
+
+    let avatar: Option<String> = interact(pool, move |conn| {
+        users::table.find(user_id).select(users::avatar).first(conn)
+    })
+    .await?;
+    if let Some(avatar) = avatar {
+        storage.delete(&avatar).await?;
+    }
+
+    Ok(interact(pool, move |conn| {
+        diesel::update(users::table.find(user_id))
+            .set(users::avatar.eq(None::<String>))
+            .returning(User::as_returning())
+            .get_result(conn)
+    })
+    .await?)
+}
+
+/// Регистрирует пользователя по приглашению администратора. Приглашение
+/// одноразовое и может быть выдано для конкретной почты.
+pub async fn register_user(
+    pool: &DbPool,
+    config: &AuthConfig,
+    registration: RegisterUser,
+) -> Result<User, UserError> {
+    use crate::schema::{invites, users};
+
+    if !config.registration_enabled {
+        return Err(UserError::RegistrationDisabled);
+    }
+    let username = valid_username(&registration.username)?;
+    let email = valid_email(&registration.email)?;
+    valid_password(&registration.password)?;
+
+    let password = registration.password;
+    let password = tokio::task::spawn_blocking(move || hash_password(&password)).await??;
+    let code = registration.invite_code;
+
+    interact_with(pool, move |conn| {
+        conn.transaction(|conn| {
+            // Блокировка строки не даёт использовать приглашение дважды
+            let invite: Invite = invites::table
+                .filter(invites::code.eq(code))
+                .select(Invite::as_select())
+                .for_update()
+                .first(conn)
+                .optional()?
+                .ok_or(UserError::InvalidInvite)?;
+            let is_valid = invite.used_at.is_none()
+                && invite
+                    .expires_at
+                    .is_none_or(|expires_at| expires_at > Utc::now())
+                && invite
+                    .email
+                    .as_ref()
+                    .is_none_or(|invite_email| invite_email.eq_ignore_ascii_case(&email));
+            if !is_valid {
+                return Err(UserError::InvalidInvite);
+            }
+
+            let user: User = diesel::insert_into(users::table)
+                .values(NewUser {
+                    username,
+                    email,
+                    password,
+                    is_admin: false,
+                })
+                .returning(User::as_returning())
+                .get_result(conn)
+                .map_err(unique_violation)?;
+
+            diesel::update(invites::table.find(invite.id))
+                .set((
+                    invites::used_by.eq(user.id),
+                    invites::used_at.eq(Utc::now()),
+                ))
+                .execute(conn)?;
+            Ok(user)
+        })
+    })
+    .await
+}
+
+/// Выдаёт приглашение для регистрации
+pub async fn create_invite(
+    pool: &DbPool,
+    admin_id: i32,
+    new_invite: NewInvite,
+) -> Result<Invite, UserError> {
+    use crate::schema::invites;
+
+    let email = new_invite.email.as_deref().map(valid_email).transpose()?;
+    if new_invite
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(UserError::InvalidExpiry);
+    }
+
+    let code = random_token();
+    Ok(interact(pool, move |conn| {
+        diesel::insert_into(invites::table)
+            .values((
+                invites::code.eq(code),
+                invites::created_by.eq(admin_id),
+                invites::email.eq(email),
+                invites::expires_at.eq(new_invite.expires_at),
+            ))
+            .returning(Invite::as_returning())
+            .get_result(conn)
+    })
+    .await?)
+}
+
+pub async fn get_invites(pool: &DbPool) -> Result<Vec<Invite>, DbError> {
+    use crate::schema::invites;
 
-    diesel::delete(users.filter(id.eq(user_id)))
-        .execute(&mut connection())
-        .expect("Error deleting posts");
+    interact(pool, move |conn| {
+        invites::table
+            .select(Invite::as_select())
+            .order(invites::id.desc())
+            .load(conn)
+    })
+    .await
 }
 
-pub async fn create_user(mut new_user: NewUser) -> User {
-    new_user.password = hash_password(&new_user.password).unwrap();
+/// Отзывает приглашение
+pub async fn delete_invite(pool: &DbPool, invite_id: i32) -> Result<(), DbError> {
+    use crate::schema::invites;
 
-    diesel::insert_into(crate::schema::users::table)
-        .values(&new_user)
-        .returning(User::as_returning())
-        .get_result(&mut connection())
-        .expect("Error save user in db")
+    interact(pool, move |conn| {
+        match diesel::delete(invites::table.find(invite_id)).execute(conn)? {
+            0 => Err(diesel::result::Error::NotFound),
+            _ => Ok(()),
+        }
+    })
+    .await
 }
@@ -0,0 +1,14 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::db_connection::DbPool;
+use crate::ml::MlBackend;
+use crate::storage::Storage;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub pool: DbPool,
+    pub ml: Arc<dyn MlBackend>,
+    pub storage: Arc<dyn Storage>,
+}
@@ -0,0 +1,98 @@
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::errors::StorageError;
+use crate::storage::{ByteRange, Storage, StoredObject};
+
+/// Файлы на локальном диске, ключ - путь относительно рабочего каталога
+#[derive(Debug, Clone, Default)]
+pub struct LocalStorage;
+
+impl LocalStorage {
+    pub fn new() -> Self {
+        LocalStorage
+    }
+}
+
+fn not_found(key: &str, err: io::Error) -> StorageError {
+    match err.kind() {
+        io::ErrorKind::NotFound => StorageError::NotFound(key.to_string()),
+        _ => err.into(),
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(
+        &self,
+        key: &str,
+        content: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<(), StorageError> {
+        if let Some(dir) = Path::new(key).parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        Ok(fs::write(key, content).await?)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        fs::read(key).await.map_err(|err| not_found(key, err))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(key).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn stream(
+        &self,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<StoredObject, StorageError> {
+        let mut file = File::open(key).await.map_err(|err| not_found(key, err))?;
+        let metadata = file.metadata().await?;
+        let size = metadata.len();
+
+        let range = match range {
+            Some(range) => Some(
+                range
+                    .resolve(size)
+                    .ok_or(StorageError::InvalidRange(size))?,
+            ),
+            None => None,
+        };
+        let (first, length) = match range {
+            Some((first, last)) => (first, last - first + 1),
+            None => (0, size),
+        };
+        file.seek(SeekFrom::Start(first)).await?;
+
+        let last_modified = metadata.modified().ok();
+        let modified_secs = last_modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+
+        Ok(StoredObject {
+            body: ReaderStream::new(file.take(length)).boxed(),
+            size,
+            range,
+            content_type: mime_guess::from_path(key).first_raw().map(str::to_string),
+            last_modified,
+            etag: Some(format!("\"{size:x}-{modified_secs:x}\"")),
+        })
+    }
+
+    fn presign(&self, _key: &str, _lifetime: Duration) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,125 @@
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+
+use crate::errors::StorageError;
+
+pub mod local;
+pub mod s3;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+/// Тело файла, читаемое частями
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Диапазон из заголовка `Range`, поддерживается только один диапазон
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=first-last`, границы включительно
+    Bounded(u64, u64),
+    /// `bytes=first-`
+    From(u64),
+    /// `bytes=-length`, последние `length` байт
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Разбирает значение заголовка `Range`. Несколько диапазонов и неверный
+    /// синтаксис дают `None`, и тогда файл отдаётся целиком.
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+        let (first, last) = spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+
+        match (first.is_empty(), last.is_empty()) {
+            (true, false) => Some(ByteRange::Suffix(last.parse().ok()?)),
+            (false, true) => Some(ByteRange::From(first.parse().ok()?)),
+            (false, false) => {
+                let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+                (first <= last).then_some(ByteRange::Bounded(first, last))
+            }
+            (true, true) => None,
+        }
+    }
+
+    /// Границы диапазона в файле размером `size`, `None` - диапазон
+    /// невыполним
+    pub fn resolve(self, size: u64) -> Option<(u64, u64)> {
+        match self {
+            ByteRange::Bounded(first, last) if first < size => Some((first, last.min(size - 1))),
+            ByteRange::From(first) if first < size => Some((first, size - 1)),
+            ByteRange::Suffix(length) if length > 0 && size > 0 => {
+                Some((size - length.min(size), size - 1))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn header_value(self) -> String {
+        match self {
+            ByteRange::Bounded(first, last) => format!("bytes={first}-{last}"),
+            ByteRange::From(first) => format!("bytes={first}-"),
+            ByteRange::Suffix(length) => format!("bytes=-{length}"),
+        }
+    }
+}
+
+/// Файл или его часть, открытые для чтения
+pub struct StoredObject {
+    pub body: ByteStream,
+    /// Размер файла целиком
+    pub size: u64,
+    /// Отдаваемые байты, включительно. `None` - файл целиком.
+    pub range: Option<(u64, u64)>,
+    pub content_type: Option<String>,
+    pub last_modified: Option<SystemTime>,
+    /// `ETag` в кавычках, как в заголовке
+    pub etag: Option<String>,
+}
+
+impl StoredObject {
+    /// Длина тела
+    pub fn content_length(&self) -> u64 {
+        match self.range {
+            Some((first, last)) => last - first + 1,
+            None => self.size,
+        }
+    }
+}
+
+/// Хранилище файлов изображений. Ключ - путь файла из настроек `storage`,
+/// например `storage/images/1.jpeg`, и он же хранится в базе.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Записывает файл, заменяя существующий
+    async fn put(
+        &self,
+        key: &str,
+        content: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError>;
+
+    /// Читает файл целиком
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Удаляет файл, отсутствие файла ошибкой не считается
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    /// Открывает файл или его диапазон для отдачи клиенту
+    async fn stream(
+        &self,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<StoredObject, StorageError>;
+
+    /// Прямая ссылка на файл, действующая `lifetime`. `None`, если хранилище
+    /// не умеет подписывать ссылки и файлы отдаёт сервер.
+    fn presign(&self, key: &str, lifetime: Duration) -> Option<String>;
+}
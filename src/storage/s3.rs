@@ -0,0 +1,277 @@
+use std::fmt::Write;
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::{header, Client, Method, RequestBuilder, Response, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use crate::config::S3Config;
+use crate::errors::StorageError;
+use crate::storage::{ByteRange, Storage, StoredObject};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Наибольший срок подписанной ссылки, допустимый в S3
+const MAX_PRESIGN_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Клиент S3 совместимого хранилища (AWS S3, MinIO и т.п.). Бакет адресуется
+/// в пути (`{endpoint}/{bucket}/{key}`), запросы подписываются AWS SigV4.
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3Config) -> Result<Self, StorageError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        let endpoint = Url::parse(config.endpoint.trim_end_matches('/'))
+            .map_err(|err| StorageError::InvalidEndpoint(err.to_string()))?;
+
+        Ok(S3Storage {
+            client,
+            endpoint,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+        })
+    }
+
+    /// Путь объекта в каноническом виде SigV4, он же путь запроса
+    fn object_path(&self, key: &str) -> String {
+        let base = self.endpoint.path().trim_end_matches('/');
+        format!(
+            "{base}/{}/{}",
+            uri_encode(&self.bucket, false),
+            uri_encode(key, true)
+        )
+    }
+
+    fn host(&self) -> String {
+        let host = self.endpoint.host_str().unwrap_or_default();
+        match self.endpoint.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        }
+    }
+
+    fn url(&self, path: &str, query: &str) -> String {
+        let mut url = format!("{}://{}{path}", self.endpoint.scheme(), self.host());
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(query);
+        }
+        url
+    }
+
+    fn scope(&self, now: DateTime<Utc>) -> String {
+        format!("{}/{}/s3/aws4_request", now.format("%Y%m%d"), self.region)
+    }
+
+    fn signature(&self, now: DateTime<Utc>, canonical_request: &str) -> String {
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            now.format("%Y%m%dT%H%M%SZ"),
+            self.scope(now),
+            hex(&Sha256::digest(canonical_request.as_bytes())),
+        );
+
+        let key = [
+            now.format("%Y%m%d").to_string().as_str(),
+            &self.region,
+            "s3",
+            "aws4_request",
+        ]
+        .into_iter()
+        .fold(
+            format!("AWS4{}", self.secret_key).into_bytes(),
+            |key, part| hmac(&key, part),
+        );
+        hex(&hmac(&key, &string_to_sign))
+    }
+
+    /// Запрос к объекту, подписанный в заголовке `Authorization`
+    fn request(&self, method: Method, key: &str, body: Option<Vec<u8>>) -> RequestBuilder {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let path = self.object_path(key);
+        let payload_hash = hex(&Sha256::digest(body.as_deref().unwrap_or_default()));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}",
+            self.host(),
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={signed_headers}, Signature={}",
+            self.access_key,
+            self.scope(now),
+            self.signature(now, &canonical_request),
+        );
+
+        let request = self
+            .client
+            .request(method, self.url(&path, ""))
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(header::AUTHORIZATION, authorization);
+        match body {
+            Some(body) => request.body(body),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, key: &str) -> Result<Response, StorageError> {
+        let response = request.send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(StorageError::NotFound(key.to_string())),
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                let size = header_str(&response, header::CONTENT_RANGE)
+                    .and_then(|value| value.strip_prefix("bytes */"))
+                    .and_then(|size| size.parse().ok())
+                    .unwrap_or_default();
+                Err(StorageError::InvalidRange(size))
+            }
+            status => Err(StorageError::Status {
+                key: key.to_string(),
+                status,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(
+        &self,
+        key: &str,
+        content: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        let request = self
+            .request(Method::PUT, key, Some(content))
+            .header(header::CONTENT_TYPE, content_type);
+        self.send(request, key).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let response = self.send(self.request(Method::GET, key, None), key).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self
+            .send(self.request(Method::DELETE, key, None), key)
+            .await
+        {
+            Err(StorageError::NotFound(_)) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    async fn stream(
+        &self,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<StoredObject, StorageError> {
+        let mut request = self.request(Method::GET, key, None);
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range.header_value());
+        }
+        let response = self.send(request, key).await?;
+
+        // `Content-Range: bytes first-last/size` есть только в ответе 206
+        let content_range = header_str(&response, header::CONTENT_RANGE)
+            .and_then(|value| value.strip_prefix("bytes "))
+            .and_then(|value| {
+                let (range, size) = value.split_once('/')?;
+                let (first, last) = range.split_once('-')?;
+                Some((
+                    (first.parse().ok()?, last.parse().ok()?),
+                    size.parse().ok()?,
+                ))
+            });
+        let (range, size) = match (response.status(), content_range) {
+            (StatusCode::PARTIAL_CONTENT, Some((range, size))) => (Some(range), size),
+            _ => (None, response.content_length().unwrap_or_default()),
+        };
+
+        Ok(StoredObject {
+            size,
+            range,
+            content_type: header_str(&response, header::CONTENT_TYPE).map(str::to_string),
+            last_modified: header_str(&response, header::LAST_MODIFIED)
+                .and_then(|value| httpdate::parse_http_date(value).ok()),
+            etag: header_str(&response, header::ETAG).map(str::to_string),
+            body: response.bytes_stream().map_err(io::Error::other).boxed(),
+        })
+    }
+
+    fn presign(&self, key: &str, lifetime: Duration) -> Option<String> {
+        let now = Utc::now();
+        let path = self.object_path(key);
+        let credential = format!("{}/{}", self.access_key, self.scope(now));
+
+        // Параметры канонического запроса идут в порядке имён
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+            uri_encode(&credential, false),
+            now.format("%Y%m%dT%H%M%SZ"),
+            lifetime.min(MAX_PRESIGN_LIFETIME).as_secs().max(1),
+        );
+        let canonical_request = format!(
+            "GET\n{path}\n{query}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+            self.host(),
+        );
+        let signature = self.signature(now, &canonical_request);
+
+        Some(self.url(&path, &format!("{query}&X-Amz-Signature={signature}")))
+    }
+}
+
+fn header_str(response: &Response, name: header::HeaderName) -> Option<&str> {
+    response.headers().get(name)?.to_str().ok()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Кодирование URI по правилам SigV4: без изменений остаются только
+/// `A-Z a-z 0-9 - _ . ~` и, в путях, `/`
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+        encoded
+    })
+}
@@ -0,0 +1,124 @@
+//! Общее для тестов, работающих с базой данных. Такие тесты помечены
+//! `#[ignore]` и запускаются на базе с применёнными миграциями:
+//!
+//! ```sh
+//! TEST_DATABASE_URL=postgres://... cargo test -- --ignored
+//! ```
+//!
+//! Каждый тест создаёт своих пользователей, поэтому база может быть общей.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum_typed_multipart::{FieldData, FieldMetadata};
+use bytes::Bytes;
+use image::{Rgb, RgbImage};
+
+use crate::config::Config;
+use crate::db_connection::{create_pool, DbPool};
+use crate::ml::FakeMlBackend;
+use crate::models::{NewUser, PhotoForm, User};
+use crate::services::facial_recognition::{create_photo, process_photo};
+use crate::services::images::encode_jpeg;
+use crate::services::users::create_user;
+use crate::storage::{LocalStorage, Storage};
+
+static NEXT_NAME: AtomicU32 = AtomicU32::new(0);
+
+/// Уникальное в пределах запуска тестов имя
+fn unique_name() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let n = NEXT_NAME.fetch_add(1, Ordering::Relaxed);
+    format!("test-{}-{nanos}-{n}", std::process::id())
+}
+
+pub struct TestEnv {
+    pub pool: DbPool,
+    pub storage: Arc<dyn Storage>,
+    pub config: Arc<Config>,
+}
+
+impl TestEnv {
+    /// Подключается к базе из `TEST_DATABASE_URL`, файлы пишутся во временный
+    /// каталог
+    pub fn new() -> Self {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must point to a migrated database");
+
+        let mut config = Config::default();
+        config.database.url = url;
+        config.database.pool_size = 2;
+        let root = std::env::temp_dir()
+            .join("recognition-tests")
+            .join(unique_name());
+        let dir = |sub: &str| root.join(sub).to_string_lossy().into_owned();
+        config.storage.root = dir("");
+        config.storage.images_dir = dir("images");
+        config.storage.faces_dir = dir("faces");
+        config.storage.originals_dir = dir("originals");
+        config.storage.renditions_dir = dir("renditions");
+        config.storage.avatars_dir = dir("avatars");
+
+        TestEnv {
+            pool: create_pool(&config.database),
+            storage: Arc::new(LocalStorage::new()),
+            config: Arc::new(config),
+        }
+    }
+
+    pub async fn create_user(&self) -> User {
+        let name = unique_name();
+        create_user(
+            &self.pool,
+            NewUser {
+                username: name.clone(),
+                email: format!("{name}@example.com"),
+                password: "password".to_string(),
+                is_admin: false,
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Загружает изображение пользователя и сразу обрабатывает его, как это
+    /// сделала бы задача
+    pub async fn upload(&self, ml: &FakeMlBackend, user_id: i32, content: Vec<u8>) -> i32 {
+        let form = PhotoForm {
+            title: None,
+            album_id: None,
+            photo_image: FieldData {
+                metadata: FieldMetadata::default(),
+                contents: Bytes::from(content),
+            },
+        };
+        let uploaded = create_photo(
+            &self.pool,
+            self.storage.clone(),
+            &self.config,
+            form,
+            user_id,
+        )
+        .await
+        .unwrap();
+        process_photo(
+            &self.pool,
+            ml,
+            self.storage.clone(),
+            self.config.clone(),
+            uploaded.photo_id,
+        )
+        .await
+        .unwrap();
+        uploaded.photo_id
+    }
+}
+
+/// Одноцветное изображение, разные цвета дают разные файлы и эмбеддинги
+pub fn test_image(color: [u8; 3]) -> Vec<u8> {
+    encode_jpeg(&RgbImage::from_pixel(64, 64, Rgb(color)), 90).unwrap()
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::db_connection::interact;
+use crate::errors::CreatePhotoError;
+use crate::models::{Job, JobKind};
+use crate::services::facial_recognition::{generate_renditions, process_photo};
+use crate::services::jobs::{claim_next_job, complete_job, fail_job};
+use crate::state::AppState;
+
+const JOB_WORKERS: usize = 2;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Запускает обработчики очереди задач из таблицы `jobs`
+pub fn spawn_workers(state: AppState) {
+    for _ in 0..JOB_WORKERS {
+        tokio::spawn(run_worker(state.clone()));
+    }
+}
+
+async fn run_worker(state: AppState) {
+    loop {
+        match interact(&state.pool, claim_next_job).await {
+            Ok(Some(job)) => handle_job(&state, job).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(err) => {
+                log::error!("Error claiming job: {err}");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn handle_job(state: &AppState, job: Job) {
+    let result = run_job(state, &job).await;
+
+    let job_id = job.id;
+    let saved = match result {
+        Ok(()) => interact(&state.pool, move |conn| complete_job(conn, job_id)).await,
+        Err(err) => {
+            log::warn!("Job {job_id} attempt {} failed: {err}", job.attempts);
+            let error = err.to_string();
+            interact(&state.pool, move |conn| fail_job(conn, &job, &error)).await
+        }
+    };
+
+    if let Err(err) = saved {
+        log::error!("Error saving job {job_id} result: {err}");
+    }
+}
+
+async fn run_job(state: &AppState, job: &Job) -> Result<(), CreatePhotoError> {
+    match job.kind {
+        JobKind::ProcessPhoto => {
+            let photo_id = job
+                .photo_id
+                .ok_or(CreatePhotoError::JobWithoutPhoto(job.id))?;
+            process_photo(
+                &state.pool,
+                state.ml.as_ref(),
+                state.storage.clone(),
+                state.config.clone(),
+                photo_id,
+            )
+            .await
+        }
+        JobKind::GenerateRenditions => {
+            let photo_id = job
+                .photo_id
+                .ok_or(CreatePhotoError::JobWithoutPhoto(job.id))?;
+            generate_renditions(
+                &state.pool,
+                state.storage.as_ref(),
+                state.config.clone(),
+                photo_id,
+            )
+            .await
+        }
+    }
+}